@@ -0,0 +1,194 @@
+// Local OpenID Connect issuer to try the OIDC login without a real identity provider.
+// Every authorization request is approved for MOCK_OIDC_EMAIL, no login page is shown.
+//
+//   cargo run --example mock_oidc_issuer
+//
+// then start the API with
+//
+//   GCA_OIDC_ISSUER=http://127.0.0.1:8090
+//   GCA_OIDC_CLIENT_ID=access-control
+//   GCA_OIDC_REDIRECT_URI=http://<api host>:<api port>/login/oidc/callback
+//
+// and open /login/oidc on the API in a browser, or follow the redirects with `curl -c jar -b jar -L`.
+// The tests of `auth::oidc` run against the same issuer, through `issuer_app`
+use axum::extract::{Query, State};
+use axum::response::Redirect;
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use rand::RngCore;
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPrivateKey;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const KID: &str = "mock-1";
+
+struct Issuer {
+    url: String,
+    email: String,
+    encoding_key: EncodingKey,
+    jwk: Value,
+    codes: Mutex<HashMap<String, AuthorizationRequest>>,
+}
+
+#[derive(Deserialize, Clone)]
+struct AuthorizationRequest {
+    client_id: String,
+    redirect_uri: String,
+    state: String,
+    nonce: Option<String>,
+    code_challenge: String,
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    code: String,
+    code_verifier: String,
+    redirect_uri: String,
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+async fn discovery(State(issuer): State<Arc<Issuer>>) -> Json<Value> {
+    Json(json!({
+        "issuer": issuer.url,
+        "authorization_endpoint": format!("{}/authorize", issuer.url),
+        "token_endpoint": format!("{}/token", issuer.url),
+        "jwks_uri": format!("{}/jwks", issuer.url),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+async fn jwks(State(issuer): State<Arc<Issuer>>) -> Json<Value> {
+    Json(json!({ "keys": [issuer.jwk] }))
+}
+
+async fn authorize(
+    State(issuer): State<Arc<Issuer>>,
+    Query(request): Query<AuthorizationRequest>,
+) -> Redirect {
+    let code = random_hex(16);
+    let redirect = format!(
+        "{}?code={}&state={}",
+        request.redirect_uri, code, request.state
+    );
+
+    issuer.codes.lock().unwrap().insert(code, request);
+
+    Redirect::to(&redirect)
+}
+
+async fn token(
+    State(issuer): State<Arc<Issuer>>,
+    Form(request): Form<TokenRequest>,
+) -> Result<Json<Value>, (axum::http::StatusCode, Json<Value>)> {
+    let invalid_grant = || {
+        (
+            axum::http::StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "invalid_grant" })),
+        )
+    };
+
+    let authorization = issuer
+        .codes
+        .lock()
+        .unwrap()
+        .remove(&request.code)
+        .ok_or_else(invalid_grant)?;
+
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(request.code_verifier.as_bytes()));
+    if challenge != authorization.code_challenge
+        || request.redirect_uri != authorization.redirect_uri
+    {
+        return Err(invalid_grant());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let claims = json!({
+        "iss": issuer.url,
+        "sub": issuer.email,
+        "aud": authorization.client_id,
+        "iat": now,
+        "exp": now + 300,
+        "nonce": authorization.nonce,
+        "email": issuer.email,
+        "email_verified": true,
+        "preferred_username": issuer.email.split('@').next(),
+    });
+
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(KID.to_string());
+    let id_token = encode(&header, &claims, &issuer.encoding_key).unwrap();
+
+    Ok(Json(json!({
+        "access_token": random_hex(16),
+        "token_type": "Bearer",
+        "expires_in": 300,
+        "id_token": id_token,
+    })))
+}
+
+// Issuer at `url` that approves every login for `email`
+pub fn issuer_app(url: String, email: String) -> Router {
+    let private_key =
+        RsaPrivateKey::new(&mut rand::thread_rng(), 2048).expect("Unable to generate RSA key");
+    let pem = private_key
+        .to_pkcs1_pem(Default::default())
+        .expect("Unable to encode RSA key");
+
+    let issuer = Arc::new(Issuer {
+        url,
+        email,
+        encoding_key: EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap(),
+        jwk: json!({
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": KID,
+            "n": URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+            "e": URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+        }),
+        codes: Mutex::new(HashMap::new()),
+    });
+
+    Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/jwks", get(jwks))
+        .route("/authorize", get(authorize))
+        .route("/token", post(token))
+        .with_state(issuer)
+}
+
+#[allow(dead_code)]
+#[tokio::main]
+async fn main() {
+    let port = env::var("MOCK_OIDC_PORT").unwrap_or_else(|_| "8090".to_string());
+    let email = env::var("MOCK_OIDC_EMAIL").unwrap_or_else(|_| "staff@example.com".to_string());
+    let url = format!("http://127.0.0.1:{}", port);
+
+    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", port))
+        .await
+        .expect("Failed to bind to address");
+
+    println!("Mock OIDC issuer on {} for {}", url, email);
+    axum::serve(listener, issuer_app(url, email)).await.unwrap();
+}
@@ -0,0 +1,13 @@
+-- Numeric PIN credentials for keypad doors. PINs are stored as keyed hashes (HMAC-SHA256),
+-- so they can be looked up directly and kept unique across the whole site
+
+CREATE TABLE users_pins (
+    id INT AUTO_INCREMENT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    pin_hash CHAR(64) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE,
+    UNIQUE (pin_hash)
+);
@@ -0,0 +1,14 @@
+-- RFID/NFC badge credentials, the status is one of 'active', 'lost' or 'revoked'
+
+CREATE TABLE credentials (
+    id INT AUTO_INCREMENT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    badge_uid VARCHAR(64) NOT NULL,
+    status VARCHAR(10) DEFAULT 'active' NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE,
+    UNIQUE (badge_uid)
+);
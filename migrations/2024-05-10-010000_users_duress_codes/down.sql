@@ -0,0 +1,6 @@
+-- Undo duress codes table and audit severity
+
+ALTER TABLE users_logs
+DROP COLUMN severity;
+
+DROP TABLE IF EXISTS users_duress_codes;
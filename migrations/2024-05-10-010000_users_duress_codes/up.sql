@@ -0,0 +1,15 @@
+-- One duress code per user, hashed the same way as the PINs so both can't collide.
+-- Audit entries get a severity so duress events can be told apart from regular ones
+
+CREATE TABLE users_duress_codes (
+    user_id INT NOT NULL PRIMARY KEY,
+    code_hash CHAR(64) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE,
+    UNIQUE (code_hash)
+);
+
+ALTER TABLE users_logs
+ADD COLUMN severity VARCHAR(10) DEFAULT 'info' NOT NULL;
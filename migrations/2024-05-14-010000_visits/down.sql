@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS visits_doors;
+DROP TABLE IF EXISTS visits;
@@ -0,0 +1,28 @@
+-- Visits created by a host user, the guest gets an access code valid for `max_uses` entries
+-- between `starts_at` and `ends_at` (local time) on the listed doors. No user account is created
+
+CREATE TABLE visits (
+    id INT AUTO_INCREMENT NOT NULL PRIMARY KEY,
+    host_user_id INT NOT NULL,
+    guest_name VARCHAR(255) NOT NULL,
+    starts_at DATETIME NOT NULL,
+    ends_at DATETIME NOT NULL,
+    code_hash CHAR(64) NOT NULL,
+    max_uses INT DEFAULT 1 NOT NULL,
+    uses INT DEFAULT 0 NOT NULL,
+    is_cancelled BOOLEAN DEFAULT false NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (host_user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE,
+    UNIQUE (code_hash)
+);
+
+CREATE TABLE visits_doors (
+    visit_id INT NOT NULL,
+    door_id INT NOT NULL,
+    FOREIGN KEY (visit_id)
+        REFERENCES visits (id)
+        ON DELETE CASCADE,
+    PRIMARY KEY (visit_id, door_id)
+);
@@ -0,0 +1,23 @@
+-- Rotating refresh tokens, stored as SHA-256 hashes. Each row keeps the jti of the access token
+-- issued alongside it, so logging out can revoke both
+
+CREATE TABLE refresh_tokens (
+    id INT AUTO_INCREMENT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    token_hash CHAR(64) NOT NULL,
+    access_jti CHAR(32) NOT NULL,
+    access_expires_at DATETIME NOT NULL,
+    expires_at DATETIME NOT NULL,
+    revoked_at DATETIME NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE,
+    UNIQUE (token_hash)
+);
+
+-- Access tokens revoked before their expiry, rows can be removed once `expires_at` has passed
+CREATE TABLE revoked_tokens (
+    jti CHAR(32) NOT NULL PRIMARY KEY,
+    expires_at DATETIME NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Undo added field actor_id
+
+ALTER TABLE users_logs
+DROP FOREIGN KEY users_logs_ibfk_2,
+DROP COLUMN actor_id;
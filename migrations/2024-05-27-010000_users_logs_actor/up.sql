@@ -0,0 +1,7 @@
+-- Who performed the logged action, null for actions coming from the doors themselves
+
+ALTER TABLE users_logs
+ADD COLUMN actor_id INT NULL,
+ADD FOREIGN KEY (actor_id)
+    REFERENCES users (id)
+    ON DELETE SET NULL;
@@ -0,0 +1,14 @@
+-- Single-use password reset tokens, stored as SHA-256 hashes since the token itself is sent by email
+
+CREATE TABLE password_reset_tokens (
+    id INT AUTO_INCREMENT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    token_hash CHAR(64) NOT NULL,
+    expires_at DATETIME NOT NULL,
+    used_at DATETIME NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE,
+    UNIQUE (token_hash)
+);
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS login_lockouts;
+
+ALTER TABLE users
+DROP COLUMN failed_login_attempts,
+DROP COLUMN locked_until;
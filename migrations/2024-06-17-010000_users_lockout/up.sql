@@ -0,0 +1,18 @@
+-- Failed password attempts since the last successful login, from any IP. The account is
+-- locked everywhere until `locked_until` once too many are reached
+ALTER TABLE users
+ADD COLUMN failed_login_attempts INT NOT NULL DEFAULT 0,
+ADD COLUMN locked_until DATETIME NULL;
+
+-- Failed password attempts per account and client IP. A lock only blocks the IP the
+-- failures came from, and is reached well before the lock of the whole account
+CREATE TABLE login_lockouts (
+    user_id INT NOT NULL,
+    ip VARCHAR(45) NOT NULL,
+    failed_attempts INT NOT NULL DEFAULT 0,
+    locked_until DATETIME NULL,
+    PRIMARY KEY (user_id, ip),
+    FOREIGN KEY (user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE
+);
@@ -0,0 +1,25 @@
+-- Single row with the password rules, editable by admins
+
+CREATE TABLE password_policy (
+    id INT NOT NULL PRIMARY KEY,
+    min_length INT NOT NULL DEFAULT 8,
+    require_uppercase BOOLEAN NOT NULL DEFAULT false,
+    require_lowercase BOOLEAN NOT NULL DEFAULT false,
+    require_digit BOOLEAN NOT NULL DEFAULT false,
+    require_symbol BOOLEAN NOT NULL DEFAULT false,
+    history_size INT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP NOT NULL
+);
+
+INSERT INTO password_policy (id) VALUES (1);
+
+-- Previous passwords of each user, as Argon2 hashes, to stop them from being reused
+CREATE TABLE password_history (
+    id INT AUTO_INCREMENT NOT NULL PRIMARY KEY,
+    user_id INT NOT NULL,
+    password_hash VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE
+);
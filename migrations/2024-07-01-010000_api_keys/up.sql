@@ -0,0 +1,19 @@
+-- Keys for integrations, stored as SHA-256 hashes. `key_prefix` is the start of the key,
+-- kept to tell keys apart without revealing them. `scopes` is a comma-separated list
+
+CREATE TABLE api_keys (
+    id INT AUTO_INCREMENT NOT NULL PRIMARY KEY,
+    name VARCHAR(100) NOT NULL,
+    key_prefix CHAR(12) NOT NULL,
+    key_hash CHAR(64) NOT NULL,
+    scopes VARCHAR(1024) NOT NULL,
+    created_by INT NULL,
+    expires_at DATETIME NULL,
+    last_used_at DATETIME NULL,
+    revoked_at DATETIME NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (created_by)
+        REFERENCES users (id)
+        ON DELETE SET NULL,
+    UNIQUE (key_hash)
+);
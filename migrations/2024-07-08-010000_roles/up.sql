@@ -0,0 +1,64 @@
+-- Roles are named sets of permissions, a user gets the permissions of all their roles.
+-- Permission names are the ones in `auth::permissions`, also used as API key scopes.
+-- Users with `is_admin` keep every permission without needing a role
+
+CREATE TABLE roles (
+    id INT AUTO_INCREMENT NOT NULL PRIMARY KEY,
+    name VARCHAR(50) NOT NULL,
+    description VARCHAR(255) NOT NULL DEFAULT '',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (name)
+);
+
+CREATE TABLE role_permissions (
+    role_id INT NOT NULL,
+    permission VARCHAR(64) NOT NULL,
+    PRIMARY KEY (role_id, permission),
+    FOREIGN KEY (role_id)
+        REFERENCES roles (id)
+        ON DELETE CASCADE
+);
+
+CREATE TABLE users_roles (
+    user_id INT NOT NULL,
+    role_id INT NOT NULL,
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (user_id)
+        REFERENCES users (id)
+        ON DELETE CASCADE,
+    FOREIGN KEY (role_id)
+        REFERENCES roles (id)
+        ON DELETE CASCADE
+);
+
+INSERT INTO roles (name, description) VALUES
+    ('admin', 'Full access'),
+    ('reception', 'Manages visitors'),
+    ('auditor', 'Reads users, schedules and logs');
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, permissions.name
+FROM roles
+CROSS JOIN (
+    SELECT 'users:read' AS name UNION ALL SELECT 'users:write'
+    UNION ALL SELECT 'schedules:read' UNION ALL SELECT 'schedules:write'
+    UNION ALL SELECT 'credentials:read' UNION ALL SELECT 'credentials:write'
+    UNION ALL SELECT 'visits:read' UNION ALL SELECT 'visits:write'
+    UNION ALL SELECT 'doors:unlock-remote' UNION ALL SELECT 'logs:read'
+    UNION ALL SELECT 'settings:read' UNION ALL SELECT 'settings:write'
+    UNION ALL SELECT 'api-keys:manage' UNION ALL SELECT 'roles:manage'
+) AS permissions
+WHERE roles.name = 'admin';
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT id, 'users:read' FROM roles WHERE name = 'reception'
+UNION ALL SELECT id, 'visits:read' FROM roles WHERE name = 'reception'
+UNION ALL SELECT id, 'visits:write' FROM roles WHERE name = 'reception';
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT id, 'users:read' FROM roles WHERE name = 'auditor'
+UNION ALL SELECT id, 'schedules:read' FROM roles WHERE name = 'auditor'
+UNION ALL SELECT id, 'credentials:read' FROM roles WHERE name = 'auditor'
+UNION ALL SELECT id, 'visits:read' FROM roles WHERE name = 'auditor'
+UNION ALL SELECT id, 'logs:read' FROM roles WHERE name = 'auditor'
+UNION ALL SELECT id, 'settings:read' FROM roles WHERE name = 'auditor';
@@ -1,71 +1,207 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
 use axum::http::StatusCode;
-use jsonwebtoken::{encode, DecodingKey, EncodingKey, Header};
+use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
 use log::error;
+use rand::{Rng, RngCore};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::env;
+use sha2::{Digest, Sha256};
 use std::time::{Duration, SystemTime};
 
-use crate::utils::errors::ControllerError;
+use crate::config::Secret;
+use crate::utils::errors::{ControllerError, ControllerErrorType};
 
-#[derive(Debug, Serialize, Deserialize)]
+pub mod current_user;
+pub mod denylist;
+pub mod door_token;
+pub mod keyring;
+pub mod oidc;
+pub mod password_policy;
+pub mod permissions;
+pub mod principal;
+pub mod totp;
+
+pub use current_user::CurrentUser;
+use keyring::Keyring;
+pub use principal::Principal;
+
+// Access tokens are short-lived, sessions are kept alive with refresh tokens
+pub const ACCESS_TOKEN_TTL_SECS: u64 = 15 * 60;
+pub const REFRESH_TOKEN_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub user_id: i32,
     pub email: String,
+    pub jti: String,
     pub iat: u64,
     pub exp: u64,
 }
 
-fn fetch_secret() -> String {
-    env::var("GCA_SECRET_KEY").expect("DATABASE_URL must be set")
+pub struct AccessToken {
+    pub token: String,
+    pub jti: String,
+    pub exp: u64,
 }
 
-pub fn generate_token(user_id: i32, email: String) -> Result<String, ControllerError> {
-    let secret = fetch_secret();
-    let current_time = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
-        Ok(n) => n.as_secs(),
+fn current_timestamp() -> Result<u64, ControllerError> {
+    match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => Ok(n.as_secs()),
         Err(_) => {
             error!("Unable to get current time, system time is before UNIX EPOCH");
-            return Err(ControllerError {
-                message: "Error generating token".to_string(),
-                status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            });
+            Err(ControllerError::localized(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "token_generation_failed",
+            ))
         }
-    };
+    }
+}
+
+// Signs the claims with the current signing key of the keyring
+fn sign_claims<T: Serialize>(keyring: &Keyring, claims: &T) -> Result<String, ControllerError> {
+    let key = keyring.signing_key();
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
+
+    let encoding_key = key.encoding_key().ok_or_else(|| {
+        ControllerError::localized(StatusCode::INTERNAL_SERVER_ERROR, "token_generation_failed")
+    })?;
+
+    encode(&header, claims, encoding_key).map_err(|_| {
+        error!("Error during token generation, check the secret key");
+        ControllerError::localized(StatusCode::INTERNAL_SERVER_ERROR, "token_generation_failed")
+    })
+}
+
+// Accepts tokens signed by any key of the keyring, as long as the algorithm matches the key.
+// Tokens with an `aud` claim are rejected unless `audience` is given and matches
+fn decode_claims<T: DeserializeOwned>(
+    keyring: &Keyring,
+    token: &str,
+    audience: Option<&str>,
+) -> Option<T> {
+    let header = decode_header(token).ok()?;
+
+    keyring
+        .validation_keys(header.kid.as_deref())
+        .into_iter()
+        .filter(|key| key.algorithm == header.alg)
+        .find_map(|key| {
+            let mut validation = Validation::new(key.algorithm);
+            if let Some(audience) = audience {
+                validation.set_audience(&[audience]);
+            }
+
+            decode::<T>(token, key.decoding_key(), &validation)
+                .map(|data| data.claims)
+                .ok()
+        })
+}
+
+pub fn generate_token(
+    keyring: &Keyring,
+    user_id: i32,
+    email: String,
+) -> Result<AccessToken, ControllerError> {
+    let current_time = current_timestamp()?;
 
-    let one_day = 60 * 60 * 24;
     let claims = Claims {
         user_id,
         email,
+        jti: generate_random_token(16),
         iat: current_time,
-        exp: current_time + Duration::from_secs(one_day).as_secs(),
+        exp: current_time + Duration::from_secs(ACCESS_TOKEN_TTL_SECS).as_secs(),
     };
 
-    match encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    ) {
-        Ok(token) => Ok(token),
-        Err(_) => {
-            error!("Error during token generation, check the secret key");
-            return Err(ControllerError {
-                message: "Error generating token".to_string(),
-                status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            });
-        }
-    }
+    let token = sign_claims(keyring, &claims)?;
+
+    Ok(AccessToken {
+        token,
+        jti: claims.jti,
+        exp: claims.exp,
+    })
 }
 
-pub fn validate_token(token: String) -> bool {
-    let secret = fetch_secret();
-    let validation = jsonwebtoken::Validation::default();
-
-    match jsonwebtoken::decode::<Claims>(
-        &token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &validation,
-    ) {
-        Ok(_) => true,
-        Err(_) => false,
-    }
+pub fn validate_token(keyring: &Keyring, token: String) -> Option<Claims> {
+    decode_claims(keyring, &token, None)
+}
+
+// Random hex string with `size` bytes of entropy, used for token ids and refresh tokens
+pub fn generate_random_token(size: usize) -> String {
+    let mut bytes = vec![0u8; size];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    hex::encode(bytes)
+}
+
+// Random code of `digits` digits for the door keypads: PINs, duress and visit codes
+pub fn generate_numeric_code(digits: usize) -> String {
+    let mut rng = rand::thread_rng();
+
+    (0..digits)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+        .collect()
+}
+
+// Tries at a random code that isn't taken, before giving up with `codes_exhausted`
+pub const CODE_ATTEMPTS: usize = 10;
+
+// Previous passwords are only compared, never looked up, so unlike the PINs they get a salted
+// slow hash. Argon2 takes tens of milliseconds, it runs on the blocking threads
+pub async fn hash_password(password: String) -> Result<String, ControllerError> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut rand::rngs::OsRng);
+
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+    })
+    .await
+    .ok()
+    .and_then(Result::ok)
+    .ok_or_else(|| ControllerError::from_type(ControllerErrorType::InternalServerError))
+}
+
+// Whether the password matches any of the hashes, each one is verified in turn
+pub async fn matches_any_password(password: String, hashes: Vec<String>) -> bool {
+    tokio::task::spawn_blocking(move || {
+        hashes.iter().any(|hash| {
+            PasswordHash::new(hash).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+        })
+    })
+    .await
+    .unwrap_or(false)
+}
+
+pub fn codes_exhausted() -> ControllerError {
+    ControllerError::localized(StatusCode::SERVICE_UNAVAILABLE, "code_generation_failed")
+}
+
+pub fn timestamp_to_datetime(timestamp: u64) -> NaiveDateTime {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+// Refresh tokens have enough entropy on their own, a plain digest is enough to store them
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// PINs are short, so they are hashed with a keyed HMAC instead of a plain digest. The same PIN
+// always yields the same hash, which allows looking users up by PIN and enforcing uniqueness.
+// The pepper is GCA_PIN_PEPPER, it can't change without invalidating every PIN stored
+pub fn hash_pin(pepper: &Secret, pin: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(pepper.expose().as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(pin.as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
 }
@@ -0,0 +1,43 @@
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+
+use super::{Claims, Principal};
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+
+// User calling an authenticated route with a JWT, API keys are rejected
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub user_id: i32,
+    pub email: String,
+    pub jti: String,
+    pub exp: u64,
+}
+
+impl From<Claims> for CurrentUser {
+    fn from(claims: Claims) -> Self {
+        Self {
+            user_id: claims.user_id,
+            email: claims.email,
+            jti: claims.jti,
+            exp: claims.exp,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentUser
+where
+    S: Send + Sync,
+{
+    type Rejection = ControllerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        match parts.extensions.get::<Principal>() {
+            Some(Principal::User(user)) => Ok(user.clone()),
+            _ => Err(ControllerError::from_type(
+                ControllerErrorType::Unauthorized,
+            )),
+        }
+    }
+}
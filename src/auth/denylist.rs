@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+use crate::models::revoked_tokens;
+use crate::utils::MappedErrors;
+
+// Tokens revoked by another instance of the API are picked up within this interval
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+// In-memory copy of the `revoked_tokens` table, checked on every authenticated request.
+// It is loaded on startup, updated by whoever revokes a token here through `insert` and
+// refreshed from the table every `REFRESH_INTERVAL` for the tokens revoked elsewhere
+pub struct TokenDenylist {
+    revoked: RwLock<HashMap<String, u64>>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|n| n.as_secs())
+        .unwrap_or_default()
+}
+
+impl TokenDenylist {
+    async fn fetch(
+        pool: &deadpool_diesel::mysql::Pool,
+    ) -> Result<HashMap<String, u64>, MappedErrors> {
+        let revoked = revoked_tokens::list_active(pool)
+            .await?
+            .into_iter()
+            .map(|(jti, expires_at)| (jti, expires_at.and_utc().timestamp() as u64))
+            .collect();
+
+        Ok(revoked)
+    }
+
+    pub async fn load(pool: &deadpool_diesel::mysql::Pool) -> Self {
+        let revoked = Self::fetch(pool)
+            .await
+            .expect("Unable to load revoked tokens");
+
+        Self {
+            revoked: RwLock::new(revoked),
+        }
+    }
+
+    // Merged with the current entries, a token revoked here while the table was read is kept
+    pub async fn refresh(&self, pool: &deadpool_diesel::mysql::Pool) -> Result<(), MappedErrors> {
+        let fetched = Self::fetch(pool).await?;
+
+        let mut revoked = self.revoked.write().unwrap();
+        let now = now();
+        revoked.retain(|_, exp| *exp > now);
+        revoked.extend(fetched);
+
+        Ok(())
+    }
+
+    pub fn is_revoked(&self, jti: &str) -> bool {
+        self.revoked.read().unwrap().contains_key(jti)
+    }
+
+    pub fn insert(&self, jti: String, exp: u64) {
+        let mut revoked = self.revoked.write().unwrap();
+        let now = now();
+
+        // Expired tokens are rejected anyway, no need to keep them around
+        revoked.retain(|_, exp| *exp > now);
+        revoked.insert(jti, exp);
+    }
+}
@@ -0,0 +1,104 @@
+use axum::http::StatusCode;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use log::{error, warn};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+use super::keyring::{ed25519_public_key_pem, pem_to_der};
+use crate::config::Config;
+use crate::utils::errors::ControllerError;
+
+// Door tokens are shown as QR codes and checked offline by the door controllers,
+// keep them short-lived since they can't be revoked
+const DOOR_TOKEN_TTL_SECS: u64 = 120;
+pub const DOOR_TOKEN_AUDIENCE: &str = "gca-door";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DoorTokenClaims {
+    pub sub: i32,
+    pub door_id: u32,
+    pub aud: String,
+    pub iat: u64,
+    pub exp: u64,
+    pub jti: String,
+}
+
+// Ed25519 key pair used only for door tokens, separate from the session JWT secret
+pub struct DoorTokenKeys {
+    encoding_key: EncodingKey,
+    public_key_pem: String,
+    public_key: Vec<u8>,
+}
+
+impl DoorTokenKeys {
+    // Loads the PKCS#8 private key PEM file, door tokens are disabled when it is not configured.
+    // The public key is derived from it, so the published key always verifies the tokens
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let private_path = match &config.door_tokens {
+            Some(keys) => &keys.private_key,
+            None => {
+                warn!("GCA_DOOR_TOKEN_PRIVATE_KEY not set, door tokens disabled");
+                return None;
+            }
+        };
+
+        let private_pem = std::fs::read_to_string(private_path)
+            .unwrap_or_else(|_| panic!("Unable to read door token private key {}", private_path));
+
+        let encoding_key = EncodingKey::from_ed_pem(private_pem.as_bytes())
+            .expect("GCA_DOOR_TOKEN_PRIVATE_KEY is not a valid Ed25519 PEM key");
+        let key_pair = pem_to_der(&private_pem)
+            .and_then(|der| Ed25519KeyPair::from_pkcs8_maybe_unchecked(&der).ok())
+            .expect("GCA_DOOR_TOKEN_PRIVATE_KEY is not a valid Ed25519 PEM key");
+        let public_key = key_pair.public_key().as_ref().to_vec();
+
+        Some(Self {
+            encoding_key,
+            public_key_pem: ed25519_public_key_pem(&public_key),
+            public_key,
+        })
+    }
+
+    pub fn public_key_pem(&self) -> &str {
+        &self.public_key_pem
+    }
+
+    // Public key as the `x` member of an OKP JWK
+    pub fn public_key_x(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.public_key)
+    }
+}
+
+pub fn generate_door_token(
+    keys: &DoorTokenKeys,
+    user_id: i32,
+    door_id: u32,
+) -> Result<String, ControllerError> {
+    let current_time = match SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(n) => n.as_secs(),
+        Err(_) => {
+            error!("Unable to get current time, system time is before UNIX EPOCH");
+            return Err(ControllerError::localized(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "token_generation_failed",
+            ));
+        }
+    };
+
+    let claims = DoorTokenClaims {
+        sub: user_id,
+        door_id,
+        aud: DOOR_TOKEN_AUDIENCE.to_string(),
+        iat: current_time,
+        exp: current_time + DOOR_TOKEN_TTL_SECS,
+        jti: super::generate_random_token(16),
+    };
+
+    encode(&Header::new(Algorithm::EdDSA), &claims, &keys.encoding_key).map_err(|_| {
+        error!("Error during door token generation, check the door token keys");
+        ControllerError::localized(StatusCode::INTERNAL_SERVER_ERROR, "token_generation_failed")
+    })
+}
@@ -0,0 +1,340 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use log::{error, info};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::config::Config;
+
+// Kid given to the key built from GCA_SECRET_KEY when no keyring file is configured
+pub const DEFAULT_KID: &str = "default";
+
+// Keyring file format (GCA_JWT_KEYRING):
+// {
+//   "signing_kid": "2024-06",
+//   "keys": [
+//     { "kid": "2024-01", "alg": "HS256", "secret": "..." },
+//     { "kid": "2024-06", "alg": "RS256", "private_key": "/path/key.pem", "public_key": "/path/key.pub.pem" },
+//     { "kid": "partner", "alg": "EdDSA", "public_key": "/path/partner.pub.pem" }
+//   ]
+// }
+// Keys without a private key (or secret) are only used to validate tokens
+#[derive(Deserialize)]
+struct KeyringFile {
+    signing_kid: String,
+    keys: Vec<KeyFile>,
+}
+
+#[derive(Deserialize)]
+struct KeyFile {
+    kid: String,
+    alg: Algorithm,
+    secret: Option<String>,
+    private_key: Option<String>,
+    public_key: Option<String>,
+}
+
+pub struct Key {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+    jwk: Option<Value>,
+}
+
+impl Key {
+    pub fn encoding_key(&self) -> Option<&EncodingKey> {
+        self.encoding_key.as_ref()
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+pub struct Keyring {
+    signing_kid: String,
+    keys: HashMap<String, Key>,
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|err| format!("Unable to read key file {}: {}", path, err))
+}
+
+// An Ed25519 SubjectPublicKeyInfo is this fixed header, with the id-Ed25519 OID
+// (1.3.101.112), followed by the 32 bytes key
+const ED25519_SPKI_HEADER: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+// DER content of a PEM file
+pub fn pem_to_der(pem: &str) -> Option<Vec<u8>> {
+    let body: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+
+    STANDARD.decode(body.trim()).ok()
+}
+
+pub fn ed25519_raw_public_key(pem: &str) -> Option<Vec<u8>> {
+    let der = pem_to_der(pem)?;
+    let key = der.strip_prefix(&ED25519_SPKI_HEADER)?;
+
+    (key.len() == 32).then(|| key.to_vec())
+}
+
+pub fn ed25519_public_key_pem(raw_public_key: &[u8]) -> String {
+    let der = [&ED25519_SPKI_HEADER[..], raw_public_key].concat();
+
+    format!(
+        "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n",
+        STANDARD.encode(der)
+    )
+}
+
+fn rsa_jwk(kid: &str, alg: Algorithm, pem: &str) -> Result<Value, String> {
+    let public_key = RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .map_err(|_| format!("Key '{}' has an invalid RSA public key", kid))?;
+
+    Ok(json!({
+        "kty": "RSA",
+        "kid": kid,
+        "alg": alg,
+        "use": "sig",
+        "n": URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+        "e": URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+    }))
+}
+
+fn ed25519_jwk(kid: &str, pem: &str) -> Result<Value, String> {
+    let public_key = ed25519_raw_public_key(pem)
+        .ok_or_else(|| format!("Key '{}' has an invalid Ed25519 public key", kid))?;
+
+    Ok(json!({
+        "kty": "OKP",
+        "crv": "Ed25519",
+        "kid": kid,
+        "alg": "EdDSA",
+        "use": "sig",
+        "x": URL_SAFE_NO_PAD.encode(public_key),
+    }))
+}
+
+impl Key {
+    fn hmac(kid: String, algorithm: Algorithm, secret: &[u8]) -> Self {
+        Self {
+            kid,
+            algorithm,
+            encoding_key: Some(EncodingKey::from_secret(secret)),
+            decoding_key: DecodingKey::from_secret(secret),
+            jwk: None,
+        }
+    }
+
+    fn from_file(key: KeyFile) -> Result<Self, String> {
+        let kid = key.kid;
+
+        match key.alg {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                let secret = key
+                    .secret
+                    .ok_or_else(|| format!("Key '{}' needs a secret", kid))?;
+
+                Ok(Self::hmac(kid, key.alg, secret.as_bytes()))
+            }
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 | Algorithm::EdDSA => {
+                let is_rsa = key.alg != Algorithm::EdDSA;
+                let public_path = key
+                    .public_key
+                    .ok_or_else(|| format!("Key '{}' needs a public_key", kid))?;
+                let public_pem = read_file(&public_path)?;
+
+                let decoding_key = if is_rsa {
+                    DecodingKey::from_rsa_pem(&public_pem)
+                } else {
+                    DecodingKey::from_ed_pem(&public_pem)
+                }
+                .map_err(|err| format!("Key '{}' has an invalid public key: {}", kid, err))?;
+
+                let encoding_key = match key.private_key {
+                    Some(private_path) => {
+                        let private_pem = read_file(&private_path)?;
+                        let encoding_key = if is_rsa {
+                            EncodingKey::from_rsa_pem(&private_pem)
+                        } else {
+                            EncodingKey::from_ed_pem(&private_pem)
+                        }
+                        .map_err(|err| {
+                            format!("Key '{}' has an invalid private key: {}", kid, err)
+                        })?;
+
+                        Some(encoding_key)
+                    }
+                    None => None,
+                };
+
+                let public_pem = String::from_utf8_lossy(&public_pem);
+                let jwk = if is_rsa {
+                    rsa_jwk(&kid, key.alg, &public_pem)?
+                } else {
+                    ed25519_jwk(&kid, &public_pem)?
+                };
+
+                Ok(Self {
+                    kid,
+                    algorithm: key.alg,
+                    encoding_key,
+                    decoding_key,
+                    jwk: Some(jwk),
+                })
+            }
+            alg => Err(format!(
+                "Key '{}' uses the unsupported algorithm {:?}",
+                kid, alg
+            )),
+        }
+    }
+}
+
+impl Keyring {
+    // Reads the keyring file set on GCA_JWT_KEYRING, without it the single HS256 secret
+    // from GCA_SECRET_KEY is used, so existing deployments keep working
+    pub fn load(config: &Config) -> Result<Self, String> {
+        let path = match &config.jwt_keyring {
+            Some(path) => path,
+            None => {
+                let key = Key::hmac(
+                    DEFAULT_KID.to_string(),
+                    Algorithm::HS256,
+                    config.secret_key.expose().as_bytes(),
+                );
+
+                return Ok(Self {
+                    signing_kid: DEFAULT_KID.to_string(),
+                    keys: HashMap::from([(DEFAULT_KID.to_string(), key)]),
+                });
+            }
+        };
+
+        let content = read_file(path)?;
+        let file: KeyringFile = serde_json::from_slice(&content)
+            .map_err(|err| format!("Invalid keyring file {}: {}", path, err))?;
+
+        let mut keys = HashMap::new();
+        for key in file.keys {
+            let key = Key::from_file(key)?;
+            if keys.contains_key(&key.kid) {
+                return Err(format!("Duplicated kid '{}' on the keyring", key.kid));
+            }
+            keys.insert(key.kid.clone(), key);
+        }
+
+        match keys.get(&file.signing_kid) {
+            Some(key) if key.encoding_key.is_some() => {}
+            Some(_) => {
+                return Err(format!(
+                    "Signing key '{}' has no private key",
+                    file.signing_kid
+                ))
+            }
+            None => return Err(format!("Signing key '{}' not found", file.signing_kid)),
+        }
+
+        Ok(Self {
+            signing_kid: file.signing_kid,
+            keys,
+        })
+    }
+
+    pub fn signing_key(&self) -> &Key {
+        &self.keys[&self.signing_kid]
+    }
+
+    // Tokens issued before the keyring existed have no kid, they are checked against every key
+    pub fn validation_keys(&self, kid: Option<&str>) -> Vec<&Key> {
+        match kid {
+            Some(kid) => self.keys.get(kid).into_iter().collect(),
+            None => self.keys.values().collect(),
+        }
+    }
+
+    // JSON Web Key Set with the public keys, HMAC secrets are never published
+    pub fn jwks(&self) -> Value {
+        let keys: Vec<&Value> = self
+            .keys
+            .values()
+            .filter_map(|key| key.jwk.as_ref())
+            .collect();
+
+        json!({ "keys": keys })
+    }
+}
+
+// Shared handle to the current keyring, swapped as a whole when the keys are reloaded
+pub struct KeyringHandle {
+    current: RwLock<Arc<Keyring>>,
+}
+
+impl KeyringHandle {
+    pub fn new(keyring: Keyring) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(keyring)),
+        }
+    }
+
+    pub fn get(&self) -> Arc<Keyring> {
+        self.current.read().unwrap().clone()
+    }
+
+    // On failure the keys in use are kept, a broken file never takes the API down
+    pub fn reload(&self, config: &Config) {
+        match Keyring::load(config) {
+            Ok(keyring) => {
+                info!(
+                    "JWT keyring reloaded, signing with '{}'",
+                    keyring.signing_kid
+                );
+                *self.current.write().unwrap() = Arc::new(keyring);
+            }
+            Err(err) => error!("Unable to reload the JWT keyring: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ed25519_public_key_pem, ed25519_raw_public_key, ED25519_SPKI_HEADER};
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    #[test]
+    fn public_key_pem_round_trip() {
+        let key = [7u8; 32];
+        let pem = ed25519_public_key_pem(&key);
+
+        assert_eq!(ed25519_raw_public_key(&pem), Some(key.to_vec()));
+    }
+
+    #[test]
+    fn rejects_other_key_types() {
+        // Same length as an Ed25519 key, but X25519 (1.3.101.110)
+        let mut der = ED25519_SPKI_HEADER.to_vec();
+        der[8] = 0x6e;
+        der.extend([7u8; 32]);
+        let pem = format!(
+            "-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n",
+            STANDARD.encode(der)
+        );
+
+        assert_eq!(ed25519_raw_public_key(&pem), None);
+    }
+}
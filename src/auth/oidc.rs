@@ -0,0 +1,508 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
+use url::Url;
+
+use super::generate_random_token;
+use crate::config::{Config, Secret};
+
+// The issuer of the example, the tests log in against it
+#[cfg(test)]
+#[path = "../../examples/mock_oidc_issuer.rs"]
+mod mock_oidc_issuer;
+
+// Time the user has to come back from the identity provider
+const PENDING_LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+// Cookie that carries the pending login to the callback, in the browser that started it
+pub const PENDING_LOGIN_COOKIE: &str = "gca_oidc_login";
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<Secret>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    // Users unknown to us are created on their first login instead of refused
+    pub create_users: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+// Claims of the ID token we rely on
+#[derive(Deserialize, Debug)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub preferred_username: Option<String>,
+    nonce: Option<String>,
+}
+
+// Nothing is kept on the server while the user is at the provider. The login travels signed in
+// a cookie, so only the browser that started it can finish it and no state can pile up here
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    state: String,
+    code_verifier: String,
+    nonce: String,
+    expires_at: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn cookie_mac(key: &Secret, payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.expose().as_bytes())
+        .expect("HMAC accepts keys of any size");
+    mac.update(PENDING_LOGIN_COOKIE.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+impl PendingLogin {
+    fn seal(&self, key: &Secret) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default());
+        let signature = hex::encode(cookie_mac(key, &payload).finalize().into_bytes());
+
+        format!("{}.{}", payload, signature)
+    }
+
+    // The login of the cookie, if it's ours, not expired and for this state
+    fn open(cookie: &str, key: &Secret, state: &str) -> Result<Self, OidcError> {
+        let (payload, signature) = cookie.split_once('.').ok_or(OidcError::UnknownState)?;
+        let signature = hex::decode(signature).map_err(|_| OidcError::UnknownState)?;
+
+        cookie_mac(key, payload)
+            .verify_slice(&signature)
+            .map_err(|_| OidcError::UnknownState)?;
+
+        let login: PendingLogin = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or(OidcError::UnknownState)?;
+
+        if login.state != state || login.expires_at <= now() {
+            return Err(OidcError::UnknownState);
+        }
+
+        Ok(login)
+    }
+}
+
+#[derive(Debug)]
+pub enum OidcError {
+    Provider(String),
+    UnknownState,
+    InvalidIdToken,
+    EmailNotVerified,
+}
+
+impl fmt::Display for OidcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OidcError::Provider(err) => write!(f, "Identity provider error: {}", err),
+            OidcError::UnknownState => write!(f, "Unknown or expired login state"),
+            OidcError::InvalidIdToken => write!(f, "Invalid ID token"),
+            OidcError::EmailNotVerified => write!(f, "Email not verified by the provider"),
+        }
+    }
+}
+
+// Authorization code flow with PKCE against the issuer given in GCA_OIDC_ISSUER.
+// The provider endpoints come from its discovery document, fetched on first use
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    // Signs the pending login cookies
+    cookie_key: Secret,
+}
+
+fn provider_error(err: reqwest::Error) -> OidcError {
+    error!("Error calling the identity provider: {}", err);
+    OidcError::Provider(err.to_string())
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig, cookie_key: Secret) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Unable to build the HTTP client"),
+            metadata: OnceCell::new(),
+            cookie_key,
+        }
+    }
+
+    // OIDC login is disabled when the issuer isn't configured
+    pub fn from_config(config: &Config) -> Option<Self> {
+        match &config.oidc {
+            Some(oidc) => Some(Self::new(oidc.clone(), config.secret_key.clone())),
+            None => {
+                warn!("GCA_OIDC_ISSUER/GCA_OIDC_CLIENT_ID/GCA_OIDC_REDIRECT_URI not set, OIDC login disabled");
+                None
+            }
+        }
+    }
+
+    pub fn create_users(&self) -> bool {
+        self.config.create_users
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata, OidcError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.config.issuer);
+                let metadata = self
+                    .http
+                    .get(url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(provider_error)?
+                    .json::<ProviderMetadata>()
+                    .await
+                    .map_err(provider_error)?;
+
+                if metadata.issuer.trim_end_matches('/') != self.config.issuer {
+                    error!(
+                        "Discovery document of {} is for issuer {}",
+                        self.config.issuer, metadata.issuer
+                    );
+                    return Err(OidcError::Provider("Issuer mismatch".to_string()));
+                }
+
+                Ok(metadata)
+            })
+            .await
+    }
+
+    // URL of the provider to send the user to, with the value of the pending login cookie.
+    // The state ties the callback to the login of the cookie
+    pub async fn authorization_url(&self) -> Result<(String, String), OidcError> {
+        let metadata = self.metadata().await?;
+
+        let state = generate_random_token(16);
+        let nonce = generate_random_token(16);
+        let code_verifier = generate_random_token(32);
+
+        let url = Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_uri.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", code_challenge(&code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|err| OidcError::Provider(err.to_string()))?;
+
+        let login = PendingLogin {
+            state,
+            code_verifier,
+            nonce,
+            expires_at: now() + PENDING_LOGIN_TTL.as_secs(),
+        };
+
+        Ok((url.into(), login.seal(&self.cookie_key)))
+    }
+
+    // Set-Cookie header for the pending login, an empty value removes the cookie. It is only
+    // sent to the callback, whose path is the one of the redirect URI, with or without the API
+    // prefix. As the provider redirects with a top-level GET, Lax is enough
+    pub fn pending_login_cookie(&self, value: &str) -> String {
+        let max_age = if value.is_empty() {
+            0
+        } else {
+            PENDING_LOGIN_TTL.as_secs()
+        };
+        let secure = if self.config.redirect_uri.starts_with("https://") {
+            "; Secure"
+        } else {
+            ""
+        };
+        let path = Url::parse(&self.config.redirect_uri)
+            .map(|url| url.path().to_string())
+            .unwrap_or_else(|_| "/".to_string());
+
+        format!(
+            "{}={}; Max-Age={}; Path={}; HttpOnly; SameSite=Lax{}",
+            PENDING_LOGIN_COOKIE, value, max_age, path, secure
+        )
+    }
+
+    // Exchanges the code given to the callback, the claims are only returned once the
+    // ID token signature, issuer, audience and nonce are checked
+    pub async fn finish_login(
+        &self,
+        code: &str,
+        state: &str,
+        cookie: Option<&str>,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let cookie = cookie.ok_or(OidcError::UnknownState)?;
+        let login = PendingLogin::open(cookie, &self.cookie_key, state)?;
+
+        let metadata = self.metadata().await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_uri.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", login.code_verifier.as_str()),
+        ];
+        if let Some(client_secret) = &self.config.client_secret {
+            form.push(("client_secret", client_secret.expose()));
+        }
+
+        let tokens = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(provider_error)?
+            .json::<TokenResponse>()
+            .await
+            .map_err(provider_error)?;
+
+        let claims = self.validate_id_token(metadata, &tokens.id_token).await?;
+
+        if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+            warn!("ID token of {} with a wrong nonce", claims.sub);
+            return Err(OidcError::InvalidIdToken);
+        }
+
+        // Providers that don't say the email is verified are not trusted with it
+        if claims.email.is_none() || claims.email_verified != Some(true) {
+            return Err(OidcError::EmailNotVerified);
+        }
+
+        Ok(claims)
+    }
+
+    // The keys are fetched on every login, so key rotations on the provider side just work
+    async fn validate_id_token(
+        &self,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let header = decode_header(id_token).map_err(|_| OidcError::InvalidIdToken)?;
+
+        // Only asymmetric signatures, the client secret is not a signing key here
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(OidcError::InvalidIdToken);
+        }
+
+        let jwks = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(provider_error)?
+            .json::<JwkSet>()
+            .await
+            .map_err(provider_error)?;
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or(OidcError::InvalidIdToken)?;
+
+        let decoding_key = DecodingKey::from_jwk(jwk).map_err(|_| OidcError::InvalidIdToken)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        decode::<IdTokenClaims>(id_token, &decoding_key, &validation)
+            .map(|data| data.claims)
+            .map_err(|err| {
+                warn!("Invalid ID token: {}", err);
+                OidcError::InvalidIdToken
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes::V1_PREFIX;
+
+    const EMAIL: &str = "staff@example.com";
+    const REDIRECT_URI: &str = "http://127.0.0.1/login/oidc/callback";
+
+    fn cookie_key() -> Secret {
+        "a-secret-key-only-for-the-tests".parse().unwrap()
+    }
+
+    async fn client_with_issuer() -> OidcClient {
+        client_with_redirect_uri(REDIRECT_URI).await
+    }
+
+    async fn client_with_redirect_uri(redirect_uri: &str) -> OidcClient {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let app = mock_oidc_issuer::issuer_app(issuer.clone(), EMAIL.to_string());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        OidcClient::new(
+            OidcConfig {
+                issuer,
+                client_id: "access-control".to_string(),
+                client_secret: None,
+                redirect_uri: redirect_uri.to_string(),
+                scopes: "openid email".to_string(),
+                create_users: false,
+            },
+            cookie_key(),
+        )
+    }
+
+    // Follows the authorization URL like the browser, returns the code and state of the callback
+    async fn authorize(url: &str) -> (String, String) {
+        let response = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+            .get(url)
+            .send()
+            .await
+            .unwrap();
+        let location = Url::parse(response.headers()["location"].to_str().unwrap()).unwrap();
+        let param = |name: &str| {
+            location
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .unwrap()
+        };
+
+        (param("code"), param("state"))
+    }
+
+    #[tokio::test]
+    async fn logs_in_against_the_mock_issuer() {
+        let client = client_with_issuer().await;
+
+        let (url, cookie) = client.authorization_url().await.unwrap();
+        let (code, state) = authorize(&url).await;
+        let claims = client
+            .finish_login(&code, &state, Some(&cookie))
+            .await
+            .unwrap();
+
+        assert_eq!(claims.email.as_deref(), Some(EMAIL));
+        assert_eq!(claims.email_verified, Some(true));
+    }
+
+    #[tokio::test]
+    async fn sends_the_cookie_to_the_callback_under_the_api_prefix() {
+        let redirect_uri = format!("http://127.0.0.1{}/login/oidc/callback", V1_PREFIX);
+        let client = client_with_redirect_uri(&redirect_uri).await;
+
+        // Start: the browser keeps the cookie for the paths under its Path only
+        let (url, cookie) = client.authorization_url().await.unwrap();
+        let set_cookie = client.pending_login_cookie(&cookie);
+        let path = set_cookie
+            .split("; ")
+            .find_map(|attribute| attribute.strip_prefix("Path="))
+            .unwrap();
+        let callback_path = Url::parse(&redirect_uri).unwrap().path().to_string();
+        assert!(callback_path.starts_with(path));
+
+        // Callback: the cookie comes back with the code and state of the provider
+        let (code, state) = authorize(&url).await;
+        let claims = client
+            .finish_login(&code, &state, Some(&cookie))
+            .await
+            .unwrap();
+
+        assert_eq!(claims.email.as_deref(), Some(EMAIL));
+    }
+
+    #[tokio::test]
+    async fn refuses_the_callback_without_the_cookie_of_the_login() {
+        let client = client_with_issuer().await;
+
+        // Login CSRF: the attacker's code and state, in a browser that started another login
+        let (attacker_url, _) = client.authorization_url().await.unwrap();
+        let (_, victim_cookie) = client.authorization_url().await.unwrap();
+        let (code, state) = authorize(&attacker_url).await;
+
+        let without_cookie = client.finish_login(&code, &state, None).await;
+        let other_cookie = client
+            .finish_login(&code, &state, Some(&victim_cookie))
+            .await;
+
+        assert!(matches!(without_cookie, Err(OidcError::UnknownState)));
+        assert!(matches!(other_cookie, Err(OidcError::UnknownState)));
+    }
+
+    #[test]
+    fn refuses_tampered_and_expired_cookies() {
+        let login = PendingLogin {
+            state: "state".to_string(),
+            code_verifier: "verifier".to_string(),
+            nonce: "nonce".to_string(),
+            expires_at: now() + 60,
+        };
+        let cookie = login.seal(&cookie_key());
+        let other_key: Secret = "another-secret-key-for-the-tests".parse().unwrap();
+        let expired = PendingLogin {
+            expires_at: now() - 1,
+            ..login
+        }
+        .seal(&cookie_key());
+
+        assert!(PendingLogin::open(&cookie, &cookie_key(), "state").is_ok());
+        assert!(PendingLogin::open(&cookie, &other_key, "state").is_err());
+        assert!(PendingLogin::open(&cookie.replace('.', "a."), &cookie_key(), "state").is_err());
+        assert!(PendingLogin::open(&expired, &cookie_key(), "state").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use serde::Serialize;
+
+use crate::models::password_policy::PasswordPolicy;
+use crate::utils::i18n;
+
+// Compared in lowercase, a capital letter or two doesn't make them any less common
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+
+#[derive(Debug, Serialize)]
+pub struct PolicyViolation {
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl PolicyViolation {
+    // The message is the rule's entry in the catalog, under `password.`
+    fn new(rule: &'static str, params: &[(&str, &str)]) -> Self {
+        Self {
+            rule,
+            message: i18n::text(&format!("password.{}", rule), params),
+        }
+    }
+}
+
+fn is_common(password: &str) -> bool {
+    let password = password.to_lowercase();
+
+    COMMON_PASSWORDS.lines().any(|common| common == password)
+}
+
+// Every rule the password breaks, empty when it is accepted. Reuse is checked separately
+// since it needs the user's history
+pub fn check(policy: &PasswordPolicy, password: &str) -> Vec<PolicyViolation> {
+    let mut violations = Vec::new();
+
+    if password.chars().count() < policy.min_length as usize {
+        violations.push(PolicyViolation::new(
+            "min_length",
+            &[("min", &policy.min_length.to_string())],
+        ));
+    }
+
+    if policy.require_uppercase && !password.chars().any(|c| c.is_uppercase()) {
+        violations.push(PolicyViolation::new("uppercase", &[]));
+    }
+
+    if policy.require_lowercase && !password.chars().any(|c| c.is_lowercase()) {
+        violations.push(PolicyViolation::new("lowercase", &[]));
+    }
+
+    if policy.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+        violations.push(PolicyViolation::new("digit", &[]));
+    }
+
+    if policy.require_symbol && password.chars().all(|c| c.is_alphanumeric()) {
+        violations.push(PolicyViolation::new("symbol", &[]));
+    }
+
+    if is_common(password) {
+        violations.push(PolicyViolation::new("blacklist", &[]));
+    }
+
+    violations
+}
+
+pub fn reuse_violation(history_size: i32) -> PolicyViolation {
+    PolicyViolation::new("history", &[("count", &history_size.to_string())])
+}
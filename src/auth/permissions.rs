@@ -0,0 +1,60 @@
+use axum::http::StatusCode;
+use validator::ValidationError;
+
+use crate::utils::errors::ControllerError;
+
+// Permissions given to users through their roles, also used as API key scopes
+pub const USERS_READ: &str = "users:read";
+pub const USERS_WRITE: &str = "users:write";
+pub const SCHEDULES_READ: &str = "schedules:read";
+pub const SCHEDULES_WRITE: &str = "schedules:write";
+pub const CREDENTIALS_READ: &str = "credentials:read";
+pub const CREDENTIALS_WRITE: &str = "credentials:write";
+pub const VISITS_READ: &str = "visits:read";
+pub const VISITS_WRITE: &str = "visits:write";
+pub const DOORS_UNLOCK_REMOTE: &str = "doors:unlock-remote";
+pub const LOGS_READ: &str = "logs:read";
+pub const SETTINGS_READ: &str = "settings:read";
+pub const SETTINGS_WRITE: &str = "settings:write";
+pub const API_KEYS_MANAGE: &str = "api-keys:manage";
+pub const ROLES_MANAGE: &str = "roles:manage";
+
+pub const ALL: &[&str] = &[
+    USERS_READ,
+    USERS_WRITE,
+    SCHEDULES_READ,
+    SCHEDULES_WRITE,
+    CREDENTIALS_READ,
+    CREDENTIALS_WRITE,
+    VISITS_READ,
+    VISITS_WRITE,
+    DOORS_UNLOCK_REMOTE,
+    LOGS_READ,
+    SETTINGS_READ,
+    SETTINGS_WRITE,
+    API_KEYS_MANAGE,
+    ROLES_MANAGE,
+];
+
+pub fn validate_known(names: &[String]) -> Result<(), ValidationError> {
+    if names.iter().all(|name| ALL.contains(&name.as_str())) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("unknown_permission"))
+    }
+}
+
+// Permissions can only be handed out, to a role or an API key, by a caller that has them
+pub fn ensure_held(held: &[String], requested: &[String]) -> Result<(), ControllerError> {
+    match requested
+        .iter()
+        .find(|permission| !held.contains(permission))
+    {
+        Some(permission) => Err(ControllerError::localized_with(
+            StatusCode::FORBIDDEN,
+            "permission_not_held",
+            &[("permission", permission)],
+        )),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,60 @@
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+
+use super::CurrentUser;
+use crate::models::roles;
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+use crate::utils::MappedErrors;
+
+#[derive(Debug, Clone)]
+pub struct ApiKeyPrincipal {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<String>,
+}
+
+// Caller of an authenticated route, a user with a JWT or an integration with an API key.
+// Set by `middlewares::auth::intercept_request`
+#[derive(Debug, Clone)]
+pub enum Principal {
+    User(CurrentUser),
+    ApiKey(ApiKeyPrincipal),
+}
+
+impl Principal {
+    // User to record as the actor on the audit log, there is none for API keys
+    pub fn user_id(&self) -> Option<i32> {
+        match self {
+            Principal::User(user) => Some(user.user_id),
+            Principal::ApiKey(_) => None,
+        }
+    }
+
+    // Permissions of the caller, through the roles of the user or the scopes of the API key
+    pub async fn permissions(
+        &self,
+        pool: &deadpool_diesel::mysql::Pool,
+    ) -> Result<Vec<String>, MappedErrors> {
+        match self {
+            Principal::User(user) => roles::find_permissions_by_user(pool, user.user_id).await,
+            Principal::ApiKey(api_key) => Ok(api_key.scopes.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Principal
+where
+    S: Send + Sync,
+{
+    type Rejection = ControllerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .ok_or_else(|| ControllerError::from_type(ControllerErrorType::Unauthorized))
+    }
+}
@@ -0,0 +1,98 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::keyring::Keyring;
+use crate::utils::errors::ControllerError;
+
+const TOTP_ISSUER: &str = "GCA Access Control";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECS: u64 = 30;
+// Codes from the previous and the next step are accepted too, for clock drift
+const TOTP_SKEW_STEPS: u64 = 1;
+
+const RECOVERY_CODES_COUNT: usize = 10;
+
+// Partial token given by the login when a second factor is needed, it can only be exchanged
+// on the TOTP login routes, the auth middleware rejects it since it has an audience
+pub const MFA_TOKEN_AUDIENCE: &str = "mfa";
+const MFA_TOKEN_TTL_SECS: u64 = 5 * 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaClaims {
+    pub user_id: i32,
+    pub email: String,
+    pub aud: String,
+    pub jti: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+pub fn generate_mfa_token(
+    keyring: &Keyring,
+    user_id: i32,
+    email: String,
+) -> Result<String, ControllerError> {
+    let current_time = super::current_timestamp()?;
+
+    let claims = MfaClaims {
+        user_id,
+        email,
+        aud: MFA_TOKEN_AUDIENCE.to_string(),
+        jti: super::generate_random_token(16),
+        iat: current_time,
+        exp: current_time + MFA_TOKEN_TTL_SECS,
+    };
+
+    super::sign_claims(keyring, &claims)
+}
+
+pub fn validate_mfa_token(keyring: &Keyring, token: &str) -> Option<MfaClaims> {
+    super::decode_claims(keyring, token, Some(MFA_TOKEN_AUDIENCE))
+}
+
+// Base32 secret, as shown to the user and stored
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+pub fn build(secret: &str, email: &str) -> Result<TOTP, String> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|err| format!("Invalid TOTP secret: {:?}", err))?;
+
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP_SECS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        email.to_string(),
+    )
+    .map_err(|err| format!("Invalid TOTP parameters: {}", err))
+}
+
+// Returns the time step the code belongs to. Steps up to `last_used_step` are refused,
+// so a code can't be replayed while it is still valid
+pub fn verify_code(totp: &TOTP, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+    let now = super::current_timestamp().ok()?;
+    let current_step = now / TOTP_STEP_SECS;
+
+    (current_step.saturating_sub(TOTP_SKEW_STEPS)..=current_step + TOTP_SKEW_STEPS)
+        .map(|step| step as i64)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| totp.generate(*step as u64 * TOTP_STEP_SECS) == code)
+}
+
+// Single-use codes to log in when the authenticator is lost, e.g. "3f9a1-c07be"
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let code = format!("{:010x}", rng.gen_range(0..1u64 << 40));
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
@@ -0,0 +1,432 @@
+use lettre::message::Mailbox;
+use std::env;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::auth::oidc::OidcConfig;
+
+// Optional TOML file with the same settings, the environment wins over it. Each variable
+// has its key next to it in `Config::load`, e.g. GCA_DOOR_MQTT_HOST is `host` under `[mqtt]`
+pub const CONFIG_FILE_VAR: &str = "GCA_CONFIG_FILE";
+
+// Shortest GCA_SECRET_KEY and GCA_PIN_PEPPER accepted, they are HMAC keys
+pub const MIN_SECRET_LEN: usize = 32;
+
+// Value that must not end up on logs, printed as "***"
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"***\"")
+    }
+}
+
+impl FromStr for Secret {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Secret(value.to_string()))
+    }
+}
+
+#[derive(Debug)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub user: String,
+    pub pass: Secret,
+}
+
+// Door tokens are disabled without the private key, the public key is derived from it
+#[derive(Debug)]
+pub struct DoorTokenConfig {
+    pub private_key: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    Tls,
+    StartTls,
+    None,
+}
+
+impl FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "tls" => Ok(SmtpTls::Tls),
+            "starttls" => Ok(SmtpTls::StartTls),
+            "none" => Ok(SmtpTls::None),
+            _ => Err("expected tls, starttls or none".to_string()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    pub credentials: Option<(String, Secret)>,
+}
+
+// SMTP when its host is set, otherwise the mails are written to `dir`. One of them is required
+#[derive(Debug)]
+pub struct MailConfig {
+    pub smtp: Option<SmtpConfig>,
+    pub dir: Option<String>,
+    pub from: Option<Mailbox>,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub listen_addr: SocketAddr,
+    pub database_url: Secret,
+    pub mqtt: MqttConfig,
+    // Ids of the doors, the unlock routes refuse any other
+    pub doors: Vec<u32>,
+    // HS256 key of the sessions without a keyring, also signs the OIDC login cookies
+    pub secret_key: Secret,
+    // Pepper of the PIN, duress code, visit code, recovery code and password history hashes.
+    // Apart from the secret key, so rotating the key doesn't invalidate them
+    pub pin_pepper: Secret,
+    pub jwt_keyring: Option<String>,
+    pub door_tokens: Option<DoorTokenConfig>,
+    pub oidc: Option<OidcConfig>,
+    pub mail: MailConfig,
+    // Frontend page the password reset mails link to, with the token as `?token=`
+    pub password_reset_url: Option<String>,
+    // swagger-ui-dist files for the Swagger UI on /docs, which is off without them
+    pub swagger_ui_dir: Option<PathBuf>,
+}
+
+// Every problem found while loading, so they can all be fixed at once
+#[derive(Debug)]
+pub struct ConfigErrors(Vec<String>);
+
+impl fmt::Display for ConfigErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for error in &self.0 {
+            write!(f, "\n  - {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+struct Loader {
+    file: toml::Table,
+    errors: Vec<String>,
+}
+
+impl Loader {
+    fn new() -> Self {
+        let mut errors = Vec::new();
+        let file = match env::var(CONFIG_FILE_VAR) {
+            Ok(path) => std::fs::read_to_string(&path)
+                .map_err(|err| format!("Unable to read {}: {}", path, err))
+                .and_then(|content| {
+                    content
+                        .parse::<toml::Table>()
+                        .map_err(|err| format!("Invalid config file {}: {}", path, err))
+                })
+                .unwrap_or_else(|err| {
+                    errors.push(err);
+                    toml::Table::new()
+                }),
+            Err(_) => toml::Table::new(),
+        };
+
+        Self { file, errors }
+    }
+
+    // `key` is "table.name", scalars of the file are taken as their text
+    fn raw(&self, var: &str, key: &str) -> Option<String> {
+        if let Ok(value) = env::var(var) {
+            return Some(value);
+        }
+
+        let (table, name) = key.split_once('.')?;
+        match self.file.get(table)?.get(name)? {
+            toml::Value::String(value) => Some(value.clone()),
+            toml::Value::Integer(value) => Some(value.to_string()),
+            toml::Value::Boolean(value) => Some(value.to_string()),
+            toml::Value::Float(value) => Some(value.to_string()),
+            // Lists are the same as their comma separated text
+            toml::Value::Array(values) => Some(
+                values
+                    .iter()
+                    .map(|value| match value {
+                        toml::Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            _ => None,
+        }
+    }
+
+    fn optional<T: FromStr>(&mut self, var: &str, key: &str) -> Option<T>
+    where
+        T::Err: fmt::Display,
+    {
+        let value = self.raw(var, key)?;
+
+        match value.parse::<T>() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.errors
+                    .push(format!("Invalid {} ({}): {}", var, key, err));
+                None
+            }
+        }
+    }
+
+    fn required<T: FromStr>(&mut self, var: &str, key: &str) -> Option<T>
+    where
+        T::Err: fmt::Display,
+    {
+        if self.raw(var, key).is_none() {
+            self.errors.push(format!("{} ({}) must be set", var, key));
+        }
+
+        self.optional(var, key)
+    }
+
+    // Secrets too short to be a key are refused
+    fn required_secret(&mut self, var: &str, key: &str) -> Option<Secret> {
+        let secret: Secret = self.required(var, key)?;
+
+        if secret.expose().chars().count() < MIN_SECRET_LEN {
+            self.errors.push(format!(
+                "{} ({}) must have at least {} characters",
+                var, key, MIN_SECRET_LEN
+            ));
+            return None;
+        }
+
+        Some(secret)
+    }
+
+    // Comma separated values, at least one
+    fn required_list<T: FromStr>(&mut self, var: &str, key: &str) -> Option<Vec<T>>
+    where
+        T::Err: fmt::Display,
+    {
+        let value = match self.raw(var, key) {
+            Some(value) if !value.trim().is_empty() => value,
+            _ => {
+                self.errors.push(format!("{} ({}) must be set", var, key));
+                return None;
+            }
+        };
+
+        let mut items = Vec::new();
+        for item in value.split(',') {
+            match item.trim().parse::<T>() {
+                Ok(item) => items.push(item),
+                Err(err) => {
+                    self.errors.push(format!(
+                        "Invalid {} ({}): {}: {}",
+                        var,
+                        key,
+                        item.trim(),
+                        err
+                    ));
+                    return None;
+                }
+            }
+        }
+
+        Some(items)
+    }
+
+    // Settings that only make sense together, all of them or none
+    fn group(&mut self, settings: &[(&str, &str)]) -> Option<Vec<String>> {
+        let values = settings
+            .iter()
+            .map(|(var, key)| self.raw(var, key))
+            .collect::<Vec<_>>();
+
+        if values.iter().all(Option::is_none) {
+            return None;
+        }
+
+        let missing = settings
+            .iter()
+            .zip(&values)
+            .filter(|(_, value)| value.is_none())
+            .map(|((var, _), _)| *var)
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            let given = settings
+                .iter()
+                .zip(&values)
+                .filter(|(_, value)| value.is_some())
+                .map(|((var, _), _)| *var)
+                .collect::<Vec<_>>();
+            self.errors.push(format!(
+                "{} must be set along with {}",
+                missing.join(", "),
+                given.join(", ")
+            ));
+            return None;
+        }
+
+        Some(values.into_iter().flatten().collect())
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, ConfigErrors> {
+        let mut loader = Loader::new();
+
+        let host = loader.required::<String>("GCA_ACCESS_SERVER_HOST", "server.host");
+        let port = loader.required::<u16>("GCA_ACCESS_SERVER_PORT", "server.port");
+        let listen_addr = match (host, port) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port)
+                .parse::<SocketAddr>()
+                .map_err(|err| {
+                    loader
+                        .errors
+                        .push(format!("Invalid address {}:{}: {}", host, port, err))
+                })
+                .ok(),
+            _ => None,
+        };
+
+        let database_url = loader.required("DATABASE_URL", "database.url");
+
+        let mqtt_host = loader.required("GCA_DOOR_MQTT_HOST", "mqtt.host");
+        let mqtt_port = loader.required("GCA_DOOR_MQTT_PORT", "mqtt.port");
+        let mqtt_user = loader.required("GCA_DOOR_MQTT_USER", "mqtt.user");
+        let mqtt_pass = loader.required("GCA_DOOR_MQTT_PASS", "mqtt.pass");
+
+        let doors = loader.required_list("GCA_DOOR_IDS", "doors.ids");
+
+        let secret_key = loader.required_secret("GCA_SECRET_KEY", "auth.secret_key");
+        let pin_pepper = loader.required_secret("GCA_PIN_PEPPER", "auth.pin_pepper");
+        if secret_key.is_some() && secret_key == pin_pepper {
+            loader.errors.push(
+                "GCA_PIN_PEPPER (auth.pin_pepper) must differ from GCA_SECRET_KEY (auth.secret_key)"
+                    .to_string(),
+            );
+        }
+        let jwt_keyring = loader.optional("GCA_JWT_KEYRING", "auth.jwt_keyring");
+
+        let door_tokens = loader
+            .optional("GCA_DOOR_TOKEN_PRIVATE_KEY", "door_tokens.private_key")
+            .map(|private_key| DoorTokenConfig { private_key });
+
+        let oidc = loader
+            .group(&[
+                ("GCA_OIDC_ISSUER", "oidc.issuer"),
+                ("GCA_OIDC_CLIENT_ID", "oidc.client_id"),
+                ("GCA_OIDC_REDIRECT_URI", "oidc.redirect_uri"),
+            ])
+            .map(|values| OidcConfig {
+                issuer: values[0].trim_end_matches('/').to_string(),
+                client_id: values[1].clone(),
+                client_secret: loader.optional("GCA_OIDC_CLIENT_SECRET", "oidc.client_secret"),
+                redirect_uri: values[2].clone(),
+                scopes: loader
+                    .optional("GCA_OIDC_SCOPES", "oidc.scopes")
+                    .unwrap_or_else(|| "openid email profile".to_string()),
+                create_users: loader
+                    .optional("GCA_OIDC_CREATE_USERS", "oidc.create_users")
+                    .unwrap_or(false),
+            });
+
+        let smtp = loader
+            .optional::<String>("GCA_SMTP_HOST", "mail.smtp_host")
+            .map(|host| SmtpConfig {
+                host,
+                port: loader.optional("GCA_SMTP_PORT", "mail.smtp_port"),
+                tls: loader
+                    .optional("GCA_SMTP_TLS", "mail.smtp_tls")
+                    .unwrap_or(SmtpTls::StartTls),
+                credentials: loader
+                    .group(&[
+                        ("GCA_SMTP_USERNAME", "mail.smtp_username"),
+                        ("GCA_SMTP_PASSWORD", "mail.smtp_password"),
+                    ])
+                    .map(|values| (values[0].clone(), Secret(values[1].clone()))),
+            });
+        let mail_from = loader.optional("GCA_MAIL_FROM", "mail.from");
+        if smtp.is_some() && mail_from.is_none() {
+            loader.errors.push(
+                "GCA_MAIL_FROM (mail.from) must be set to send mails through SMTP".to_string(),
+            );
+        }
+        let mail_dir = loader.optional("GCA_MAIL_DIR", "mail.dir");
+        if smtp.is_none() && mail_dir.is_none() {
+            loader.errors.push(
+                "GCA_SMTP_HOST (mail.smtp_host) or GCA_MAIL_DIR (mail.dir) must be set".to_string(),
+            );
+        }
+        let mail = MailConfig {
+            smtp,
+            dir: mail_dir,
+            from: mail_from,
+        };
+
+        let password_reset_url =
+            loader.optional("GCA_PASSWORD_RESET_URL", "mail.password_reset_url");
+        let swagger_ui_dir = loader.optional("GCA_SWAGGER_UI_DIR", "docs.swagger_ui_dir");
+
+        match (
+            listen_addr,
+            database_url,
+            mqtt_host,
+            mqtt_port,
+            mqtt_user,
+            mqtt_pass,
+            doors,
+            secret_key,
+            pin_pepper,
+        ) {
+            (
+                Some(listen_addr),
+                Some(database_url),
+                Some(host),
+                Some(port),
+                Some(user),
+                Some(pass),
+                Some(doors),
+                Some(secret_key),
+                Some(pin_pepper),
+            ) if loader.errors.is_empty() => Ok(Self {
+                listen_addr,
+                database_url,
+                mqtt: MqttConfig {
+                    host,
+                    port,
+                    user,
+                    pass,
+                },
+                doors,
+                secret_key,
+                pin_pepper,
+                jwt_keyring,
+                door_tokens,
+                oidc,
+                mail,
+                password_reset_url,
+                swagger_ui_dir,
+            }),
+            _ => Err(ConfigErrors(loader.errors)),
+        }
+    }
+}
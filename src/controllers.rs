@@ -1,6 +1,20 @@
+pub mod api_keys;
 pub mod auth;
+pub mod credentials;
+pub mod docs;
 pub mod door;
+pub mod door_tokens;
 pub mod login;
+pub mod logs;
+pub mod me;
+pub mod oidc;
+pub mod password_policy;
+pub mod password_reset;
+pub mod roles;
 pub mod service_alive;
+pub mod totp;
 pub mod user_accesses;
 pub mod users;
+pub mod users_duress_codes;
+pub mod users_pins;
+pub mod visits;
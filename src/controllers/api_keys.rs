@@ -0,0 +1,231 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::auth::{permissions, Principal};
+use crate::models::api_keys::{self, ApiKey};
+use crate::models::user_log::{self, Severity};
+use crate::utils::errors::ControllerError;
+use crate::utils::validation::ValidatedJson;
+use crate::{auth, AppState};
+
+// Keys look like "gca_<64 hex chars>", the prefix makes them easy to spot in configs and leaks
+const API_KEY_PREFIX: &str = "gca_";
+const API_KEY_PREFIX_LEN: usize = 12;
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyResponse {
+    id: i32,
+    name: String,
+    key_prefix: String,
+    scopes: Vec<String>,
+    created_by: Option<i32>,
+    expires_at: Option<NaiveDateTime>,
+    last_used_at: Option<NaiveDateTime>,
+    revoked_at: Option<NaiveDateTime>,
+    created_at: NaiveDateTime,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            scopes: api_key.scope_list(),
+            id: api_key.id,
+            name: api_key.name,
+            key_prefix: api_key.key_prefix,
+            created_by: api_key.created_by,
+            expires_at: api_key.expires_at,
+            last_used_at: api_key.last_used_at,
+            revoked_at: api_key.revoked_at,
+            created_at: api_key.created_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeysListResponse {
+    api_keys: Vec<ApiKeyResponse>,
+}
+
+// The key is only returned once, on creation
+#[derive(Serialize, ToSchema)]
+pub struct ApiKeyCreatedResponse {
+    id: i32,
+    key: String,
+}
+
+async fn audit(
+    app_state: &AppState,
+    principal: &Principal,
+    action: String,
+) -> Result<(), ControllerError> {
+    user_log::create(
+        &app_state.db_pool,
+        None,
+        principal.user_id(),
+        action,
+        Severity::Info,
+    )
+    .await
+    .map_err(ControllerError::from)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api-keys",
+    tag = "api-keys",
+    request_body = ApiKeyCreate,
+    responses(
+        (status = 201, description = "API key created, the key is only shown here", body = ApiKeyCreatedResponse),
+        (status = 403, description = "Scope beyond the permissions of the caller", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn create_api_key(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    api_key_data: ValidatedJson<api_keys::ApiKeyCreate>,
+) -> Result<(StatusCode, Json<ApiKeyCreatedResponse>), ControllerError> {
+    let held = principal.permissions(&app_state.db_pool).await?;
+    permissions::ensure_held(&held, &api_key_data.scopes)?;
+
+    let key = format!("{}{}", API_KEY_PREFIX, auth::generate_random_token(32));
+    let key_prefix = key[..API_KEY_PREFIX_LEN].to_string();
+    let action = format!("API key '{}' created", api_key_data.name);
+
+    let id = api_keys::create(
+        &app_state.db_pool,
+        api_key_data.0,
+        key_prefix,
+        auth::hash_token(&key),
+        principal.user_id(),
+    )
+    .await?;
+
+    audit(&app_state, &principal, action).await?;
+
+    Ok((StatusCode::CREATED, Json(ApiKeyCreatedResponse { id, key })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api-keys",
+    operation_id = "list_api_keys",
+    tag = "api-keys",
+    responses(
+        (status = 200, description = "Every API key", body = ApiKeysListResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn list_all(
+    State(app_state): State<AppState>,
+) -> Result<Json<ApiKeysListResponse>, ControllerError> {
+    let api_keys = api_keys::list(&app_state.db_pool).await?;
+
+    Ok(Json(ApiKeysListResponse {
+        api_keys: api_keys.into_iter().map(ApiKeyResponse::from).collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api-keys/{id}",
+    tag = "api-keys",
+    params(
+        ("id" = u32, Path, description = "Id of the API key"),
+    ),
+    responses(
+        (status = 200, description = "The API key", body = ApiKeyResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn find_api_key(
+    State(app_state): State<AppState>,
+    Path(api_key_id): Path<u32>,
+) -> Result<Json<ApiKeyResponse>, ControllerError> {
+    let api_key = api_keys::find(&app_state.db_pool, api_key_id).await?;
+
+    Ok(Json(ApiKeyResponse::from(api_key)))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api-keys/{id}",
+    tag = "api-keys",
+    params(
+        ("id" = u32, Path, description = "Id of the API key"),
+    ),
+    request_body = ApiKeyUpdate,
+    responses(
+        (status = 204, description = "API key updated"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "Scope beyond the permissions of the caller", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn update_api_key(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path(api_key_id): Path<u32>,
+    api_key_data: ValidatedJson<api_keys::ApiKeyUpdate>,
+) -> Result<StatusCode, ControllerError> {
+    // Neither the new scopes nor the ones being replaced can go beyond the caller's
+    let api_key = api_keys::find(&app_state.db_pool, api_key_id).await?;
+    let held = principal.permissions(&app_state.db_pool).await?;
+    permissions::ensure_held(&held, &api_key.scope_list())?;
+    permissions::ensure_held(&held, &api_key_data.scopes)?;
+
+    api_keys::update(&app_state.db_pool, api_key_id, api_key_data.0).await?;
+
+    audit(
+        &app_state,
+        &principal,
+        format!("API key #{} updated", api_key_id),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api-keys/{id}",
+    tag = "api-keys",
+    params(
+        ("id" = u32, Path, description = "Id of the API key"),
+    ),
+    responses(
+        (status = 204, description = "API key revoked"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn revoke_api_key(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path(api_key_id): Path<u32>,
+) -> Result<StatusCode, ControllerError> {
+    api_keys::find(&app_state.db_pool, api_key_id).await?;
+
+    api_keys::revoke(&app_state.db_pool, api_key_id).await?;
+
+    audit(
+        &app_state,
+        &principal,
+        format!("API key #{} revoked", api_key_id),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
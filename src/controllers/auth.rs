@@ -1,54 +1,310 @@
-use axum::extract::State;
+use axum::extract::{ConnectInfo, State};
 use axum::http::StatusCode;
 use axum::Json;
 use axum_macros::debug_handler;
+use chrono::Utc;
+use log::warn;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::net::SocketAddr;
+use utoipa::ToSchema;
+use validator::Validate;
 
-use crate::models::user;
-use crate::utils::errors::ControllerError;
+use crate::auth::{totp, CurrentUser};
+use crate::controllers::login::{self, LoginFailure};
+use crate::models::refresh_tokens::{self, RefreshTokenCreate};
+use crate::models::{revoked_tokens, user, users_totp};
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+use crate::utils::validation::ValidatedJson;
 use crate::utils::MappedErrors;
 use crate::{auth, AppState};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Validate, ToSchema)]
 pub struct LoginRequest {
+    #[validate(length(min = 1))]
     pub email: String,
+    #[validate(length(min = 1))]
     pub password: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct LoginResponse {
     pub token: String,
+    pub refresh_token: String,
 }
 
+// Returned by the login instead of a session when a TOTP code is needed, the token must be
+// exchanged on /login/totp. Admins without TOTP must enroll first, on /login/totp/enroll
+#[derive(Serialize, ToSchema)]
+pub struct MfaChallenge {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub enrollment_required: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum LoginResult {
+    Session(LoginResponse),
+    MfaRequired(MfaChallenge),
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct RefreshRequest {
+    #[validate(length(min = 1))]
+    pub refresh_token: String,
+}
+
+// Issues an access token and the refresh token bound to it
+pub(crate) async fn issue_session(
+    app_state: &AppState,
+    user_id: i32,
+    email: String,
+) -> Result<LoginResponse, ControllerError> {
+    let access_token = auth::generate_token(&app_state.keyring.get(), user_id, email)?;
+    let refresh_token = auth::generate_random_token(32);
+
+    let now = Utc::now().timestamp() as u64;
+    refresh_tokens::create(
+        &app_state.db_pool,
+        RefreshTokenCreate {
+            user_id,
+            token_hash: auth::hash_token(&refresh_token),
+            access_jti: access_token.jti,
+            access_expires_at: auth::timestamp_to_datetime(access_token.exp),
+            expires_at: auth::timestamp_to_datetime(now + auth::REFRESH_TOKEN_TTL_SECS),
+        },
+    )
+    .await?;
+
+    Ok(LoginResponse {
+        token: access_token.token,
+        refresh_token,
+    })
+}
+
+// Ends a login whose first factor was checked, by password or by the identity provider.
+// TOTP is optional, except for admins
+pub(crate) async fn complete_login(
+    app_state: &AppState,
+    user_id: i32,
+    email: String,
+    is_admin: bool,
+) -> Result<LoginResult, ControllerError> {
+    let has_totp = match users_totp::find(&app_state.db_pool, user_id).await {
+        Ok(totp) => totp.confirmed_at.is_some(),
+        Err(MappedErrors::NotFound) => false,
+        Err(err) => return Err(ControllerError::from(err)),
+    };
+
+    if has_totp || is_admin {
+        let mfa_token = totp::generate_mfa_token(&app_state.keyring.get(), user_id, email)?;
+
+        return Ok(LoginResult::MfaRequired(MfaChallenge {
+            mfa_required: true,
+            mfa_token,
+            enrollment_required: !has_totp,
+        }));
+    }
+
+    let session = issue_session(app_state, user_id, email).await?;
+
+    Ok(LoginResult::Session(session))
+}
+
+pub(crate) async fn revoke_access_token(
+    app_state: &AppState,
+    jti: String,
+    exp: u64,
+) -> Result<(), ControllerError> {
+    revoked_tokens::create(
+        &app_state.db_pool,
+        jti.clone(),
+        auth::timestamp_to_datetime(exp),
+    )
+    .await?;
+
+    app_state.token_denylist.insert(jti, exp);
+
+    Ok(())
+}
+
+pub(crate) async fn revoke_all_sessions(
+    app_state: &AppState,
+    user_id: i32,
+) -> Result<(), ControllerError> {
+    let access_tokens = refresh_tokens::revoke_all(&app_state.db_pool, user_id).await?;
+
+    for (jti, expires_at) in access_tokens {
+        revoke_access_token(app_state, jti, expires_at.and_utc().timestamp() as u64).await?;
+    }
+
+    Ok(())
+}
+
+fn invalid_refresh_token() -> ControllerError {
+    ControllerError::localized(StatusCode::UNAUTHORIZED, "invalid_refresh_token")
+}
+
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Session tokens, or an MFA challenge when TOTP is enabled", body = LoginResult),
+        (status = 401, description = "Invalid email or password", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+        (status = 423, description = "Account locked", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Too many attempts", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 #[debug_handler]
 pub async fn login(
     State(app_state): State<AppState>,
-    login_data: Json<LoginRequest>,
-) -> Result<Json<LoginResponse>, ControllerError> {
-    let user = user::find_by_login_user(
-        &app_state.db_pool,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    login_data: ValidatedJson<LoginRequest>,
+) -> Result<Json<LoginResult>, ControllerError> {
+    let user = login::attempt_login(
+        &app_state,
+        addr.ip(),
         login_data.email.clone(),
         login_data.password.clone(),
     )
     .await
     .map_err(|err| match err {
-        MappedErrors::NotFound => ControllerError {
-            message: "Invalid user".to_string(),
-            status_code: StatusCode::NOT_FOUND,
-        },
-        MappedErrors::InternalServerError => ControllerError {
-            message: "Internal server error".to_string(),
-            status_code: StatusCode::BAD_REQUEST,
-        },
+        LoginFailure::Throttled => {
+            ControllerError::localized(StatusCode::TOO_MANY_REQUESTS, "too_many_attempts")
+        }
+        LoginFailure::Locked(locked_until) => ControllerError::localized_with(
+            StatusCode::LOCKED,
+            "account_locked",
+            &[("until", &locked_until.to_string())],
+        ),
+        LoginFailure::InvalidCredentials => {
+            ControllerError::localized(StatusCode::UNAUTHORIZED, "invalid_credentials")
+        }
+        LoginFailure::InternalServerError => {
+            ControllerError::from_type(ControllerErrorType::InternalServerError)
+        }
     })?;
 
-    let token = auth::generate_token(user.id, user.email);
+    let result = complete_login(&app_state, user.id, user.email, user.is_admin).await?;
+
+    Ok(Json(result))
+}
+
+// Exchanges a refresh token for a new pair, the old refresh token can't be used again
+#[utoipa::path(
+    post,
+    path = "/refresh",
+    tag = "auth",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "New session tokens", body = LoginResponse),
+        (status = 401, description = "Invalid refresh token", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn refresh(
+    State(app_state): State<AppState>,
+    refresh_data: ValidatedJson<RefreshRequest>,
+) -> Result<Json<LoginResponse>, ControllerError> {
+    let token_hash = auth::hash_token(&refresh_data.refresh_token);
+
+    let stored = refresh_tokens::find_by_hash(&app_state.db_pool, token_hash)
+        .await
+        .map_err(|err| match err {
+            MappedErrors::NotFound => invalid_refresh_token(),
+            _ => ControllerError::from(err),
+        })?;
+
+    if stored.expires_at < Utc::now().naive_utc() {
+        return Err(invalid_refresh_token());
+    }
+
+    // A rotated token being used again means it was leaked, cut off every session of the user
+    let is_revoked = stored.revoked_at.is_some()
+        || !refresh_tokens::revoke(&app_state.db_pool, stored.id).await?;
+
+    if is_revoked {
+        warn!(
+            "Reuse of refresh token #{} detected, revoking all sessions of user {}",
+            stored.id, stored.user_id
+        );
+        revoke_all_sessions(&app_state, stored.user_id).await?;
+        return Err(invalid_refresh_token());
+    }
+
+    let user = user::find(&app_state.db_pool, stored.user_id as u32)
+        .await
+        .map_err(|err| match err {
+            MappedErrors::NotFound => invalid_refresh_token(),
+            _ => ControllerError::from(err),
+        })?;
 
-    match token {
-        Ok(token) => Ok(Json(LoginResponse { token })),
-        Err(err) => Err(ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        }),
+    if !user.is_active {
+        return Err(invalid_refresh_token());
     }
+
+    let session = issue_session(&app_state, user.id, user.email).await?;
+
+    Ok(Json(session))
+}
+
+// Ends the current session, both the access token and its refresh token are revoked
+#[utoipa::path(
+    post,
+    path = "/logout",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Session revoked"),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn logout(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<StatusCode, ControllerError> {
+    refresh_tokens::revoke_by_access_jti(&app_state.db_pool, current_user.jti.clone()).await?;
+
+    revoke_access_token(&app_state, current_user.jti, current_user.exp).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/logout-all",
+    tag = "auth",
+    responses(
+        (status = 204, description = "Every session of the user revoked"),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn logout_all(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<StatusCode, ControllerError> {
+    revoke_all_sessions(&app_state, current_user.user_id).await?;
+    revoke_access_token(&app_state, current_user.jti, current_user.exp).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Public keys used to sign the session tokens, for other services validating them
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "auth",
+    responses(
+        (status = 200, description = "Public keys of the session tokens, as a JWK set", body = Object),
+    )
+)]
+#[debug_handler]
+pub async fn jwks(State(app_state): State<AppState>) -> Json<Value> {
+    Json(app_state.keyring.get().jwks())
 }
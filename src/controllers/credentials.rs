@@ -0,0 +1,198 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::auth::Principal;
+use crate::models::credentials::{self, BadgeStatus, Credential};
+use crate::models::user_log::{self, Severity};
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+use crate::utils::validation::ValidatedJson;
+use crate::utils::MappedErrors;
+use crate::AppState;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CredentialsListResponse {
+    credentials: Vec<Credential>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/{user_id}/badge",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    request_body = CredentialCreate,
+    responses(
+        (status = 201, description = "Badge issued"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Badge already issued", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn create_credential(
+    State(app_state): State<AppState>,
+    Path(user_id): Path<u32>,
+    credential_data: ValidatedJson<credentials::CredentialCreate>,
+) -> Result<StatusCode, ControllerError> {
+    let badge_uid = credentials::normalize_uid(&credential_data.badge_uid);
+
+    match credentials::find_by_uid(&app_state.db_pool, badge_uid.clone()).await {
+        Err(MappedErrors::NotFound) => {}
+        Ok(_) => {
+            return Err(ControllerError::localized(
+                StatusCode::CONFLICT,
+                "badge_already_issued",
+            ))
+        }
+        Err(err) => return Err(ControllerError::from(err)),
+    }
+
+    credentials::create(&app_state.db_pool, user_id, badge_uid).await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/{user_id}/badge",
+    operation_id = "list_user_badges",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "Badges of the user", body = CredentialsListResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn find_by_user(
+    State(app_state): State<AppState>,
+    Path(user_id): Path<u32>,
+) -> Result<Json<CredentialsListResponse>, ControllerError> {
+    let credentials = credentials::find(&app_state.db_pool, user_id).await?;
+
+    Ok(Json(CredentialsListResponse { credentials }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/{user_id}/badge/{badge_id}",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+        ("badge_id" = u32, Path, description = "Id of the badge"),
+    ),
+    request_body = CredentialUpdate,
+    responses(
+        (status = 204, description = "Badge updated"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Badge revoked", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn update_credential(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path((user_id, credential_id)): Path<(u32, u32)>,
+    credential_data: ValidatedJson<credentials::CredentialUpdate>,
+) -> Result<StatusCode, ControllerError> {
+    change_status(
+        &app_state,
+        &principal,
+        user_id,
+        credential_id,
+        credential_data.status,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Badges are never removed, revoking keeps the UID reserved and the history intact
+#[utoipa::path(
+    delete,
+    path = "/user/{user_id}/badge/{badge_id}",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+        ("badge_id" = u32, Path, description = "Id of the badge"),
+    ),
+    responses(
+        (status = 200, description = "Badge revoked"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn revoke_credential(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path((user_id, credential_id)): Path<(u32, u32)>,
+) -> Result<StatusCode, ControllerError> {
+    change_status(
+        &app_state,
+        &principal,
+        user_id,
+        credential_id,
+        BadgeStatus::Revoked,
+    )
+    .await?;
+
+    Ok(StatusCode::OK)
+}
+
+// Revoked is final, a revoked badge can't be reactivated or marked as lost. Every change is
+// audited, reactivating a lost badge as much as losing it
+async fn change_status(
+    app_state: &AppState,
+    principal: &Principal,
+    user_id: u32,
+    credential_id: u32,
+    status: BadgeStatus,
+) -> Result<(), ControllerError> {
+    let credential = credentials::find_one(&app_state.db_pool, user_id, credential_id).await?;
+    let current = BadgeStatus::parse(&credential.status)
+        .ok_or_else(|| ControllerError::from_type(ControllerErrorType::InternalServerError))?;
+
+    if current == status {
+        return Ok(());
+    }
+
+    if current == BadgeStatus::Revoked {
+        return Err(ControllerError::localized(
+            StatusCode::CONFLICT,
+            "badge_revoked",
+        ));
+    }
+
+    credentials::update_status(&app_state.db_pool, user_id, credential_id, current, status).await?;
+
+    let severity = match status {
+        BadgeStatus::Active => Severity::Info,
+        BadgeStatus::Lost | BadgeStatus::Revoked => Severity::Warning,
+    };
+    user_log::create(
+        &app_state.db_pool,
+        Some(user_id as i32),
+        principal.user_id(),
+        format!(
+            "Badge #{} changed from {} to {}",
+            credential_id,
+            current.as_str(),
+            status.as_str()
+        ),
+        severity,
+    )
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::Json;
+use log::warn;
+use std::sync::Arc;
+use utoipa::OpenApi;
+
+use crate::config::Config;
+use crate::openapi::ApiDoc;
+use crate::utils::errors::ControllerError;
+
+// Swagger UI pointed at the document below. Its files are served by us from GCA_SWAGGER_UI_DIR,
+// no script is loaded from a third party
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8" />
+    <title>Access Control API</title>
+    <link rel="stylesheet" href="/api/v1/docs/swagger-ui.css" />
+</head>
+<body>
+    <div id="swagger-ui"></div>
+    <script src="/api/v1/docs/swagger-ui-bundle.js"></script>
+    <script>
+        window.onload = () => {
+            window.ui = SwaggerUIBundle({ url: "/api/v1/openapi.json", dom_id: "#swagger-ui" });
+        };
+    </script>
+</body>
+</html>
+"##;
+
+// The only files of swagger-ui-dist the page needs, nothing else of the directory is served
+const SWAGGER_UI_FILES: &[(&str, &str)] = &[
+    ("swagger-ui.css", "text/css; charset=utf-8"),
+    ("swagger-ui-bundle.js", "text/javascript; charset=utf-8"),
+];
+
+fn swagger_ui_disabled() -> ControllerError {
+    ControllerError::localized(StatusCode::NOT_FOUND, "swagger_ui_disabled")
+}
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+pub async fn swagger_ui(
+    State(config): State<Arc<Config>>,
+) -> Result<Html<&'static str>, ControllerError> {
+    match config.swagger_ui_dir {
+        Some(_) => Ok(Html(SWAGGER_UI)),
+        None => Err(swagger_ui_disabled()),
+    }
+}
+
+pub async fn swagger_ui_file(
+    State(config): State<Arc<Config>>,
+    Path(file): Path<String>,
+) -> Result<Response, ControllerError> {
+    let dir = config
+        .swagger_ui_dir
+        .as_ref()
+        .ok_or_else(swagger_ui_disabled)?;
+    let (name, content_type) = SWAGGER_UI_FILES
+        .iter()
+        .find(|(name, _)| *name == file)
+        .ok_or_else(|| ControllerError::from_status(StatusCode::NOT_FOUND))?;
+
+    let content = tokio::fs::read(dir.join(name)).await.map_err(|err| {
+        warn!("Unable to read {} from {}: {}", name, dir.display(), err);
+        ControllerError::from_status(StatusCode::NOT_FOUND)
+    })?;
+
+    Ok(([(header::CONTENT_TYPE, *content_type)], content).into_response())
+}
@@ -1,78 +1,131 @@
-use axum::extract::{self, FromRequest, Request, State};
-use axum::{async_trait, body::Bytes, http::StatusCode, Json};
+use axum::extract::{ConnectInfo, Path, State};
+use axum::{http::StatusCode, Json};
 use axum_macros::debug_handler;
+use chrono::Utc;
+use chrono_tz::Brazil;
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use utoipa::ToSchema;
 use validator::Validate;
 
-use crate::models::{user, users_accesses};
+use crate::controllers::login::{self, LoginFailure};
+use crate::models::user_log::{self, Severity};
+use crate::models::{credentials, users_accesses, users_duress_codes, users_pins, visits};
 use crate::services::mqtt;
+use crate::services::throttle::FailureLimiter;
 use crate::utils::{
     errors::{ControllerError, ControllerErrorType},
-    Response,
+    i18n,
+    validation::ValidatedJson,
+    MappedErrors, Response,
 };
-use crate::AppState;
+use crate::{auth, AppState};
 
-#[derive(Serialize, Debug, Deserialize, Validate)]
+#[derive(Serialize, Debug, Deserialize, Validate, ToSchema)]
 pub struct UserAuth {
-    #[validate(email(message = "Email inválido"))]
+    #[validate(email)]
     email: String,
 
-    #[validate(length(min = 8, message = "Senha deve ter no mínimo 8 caracteres"))]
+    // Shorter than the passwords, a duress code is accepted here too
+    #[validate(length(min = 6))]
     password: String,
 }
 
-// Make impl for FromRequest to validate body fields
-#[async_trait]
-impl<S> FromRequest<S> for UserAuth
-where
-    Bytes: FromRequest<S>,
-    S: Send + Sync,
-{
-    type Rejection = ControllerError;
-
-    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
-        let user = extract::Json::<UserAuth>::from_request(request, state)
-            .await
-            .map(|Json(user)| user)
-            .map_err(|error| ControllerError::validation_error(error.to_string()))?;
-
-        // Validate body
-        if let Err(_) = user.validate() {
-            return Err(ControllerError::from_type(
-                ControllerErrorType::BodyParsingError,
-            ));
-        }
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct PinAuth {
+    #[validate(length(min = 6, max = 12), custom = "users_pins::validate_digits")]
+    pin: String,
+}
 
-        Ok(user)
-    }
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct BadgeAuth {
+    door_id: u32,
+    #[validate(custom = "credentials::validate_uid")]
+    badge_uid: String,
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct VisitAuth {
+    #[validate(length(min = 6, max = 12), custom = "users_pins::validate_digits")]
+    code: String,
 }
 
+#[utoipa::path(
+    post,
+    path = "/validate-password",
+    tag = "doors",
+    request_body = UserAuth,
+    responses(
+        (status = 200, description = "Door unlocked", body = Response),
+        (status = 401, description = "Invalid credentials or no access at the moment", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+        (status = 423, description = "Account locked", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Too many attempts", body = Problem, content_type = "application/problem+json"),
+    )
+)]
 #[debug_handler]
 pub async fn unlock(
     State(state): State<AppState>,
-    user: UserAuth,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ValidatedJson(user): ValidatedJson<UserAuth>,
 ) -> Result<Json<Response>, ControllerError> {
+    // A duress code given as the password opens the door before the throttling and the lockout,
+    // so whoever is forcing the user can't have it refused. Misses still count as failures
+    if !past_duress_cap(&state.login_attempts, &addr.ip().to_string()) {
+        let code_hash = auth::hash_pin(&state.config.pin_pepper, &user.password);
+        match users_duress_codes::find_user_by_email_and_code(
+            &state.db_pool,
+            user.email.clone(),
+            code_hash,
+        )
+        .await
+        {
+            Ok(user_id) => return unlock_under_duress(&state, user_id, None).await,
+            Err(MappedErrors::NotFound) => {}
+            Err(_) => {
+                return Err(ControllerError::from_type(
+                    ControllerErrorType::InternalServerError,
+                ))
+            }
+        }
+    }
+
     // Find user by email and password, if not found return unauthorized
     let user_search =
-        user::find_by_login(&state.db_pool, user.email.clone(), user.password.clone()).await;
+        login::attempt_login(&state, addr.ip(), user.email.clone(), user.password.clone()).await;
     let user_id = match user_search {
+        Err(LoginFailure::Throttled) => {
+            login::register_refused(&state, addr.ip(), user.email).await;
+            return Err(ControllerError::localized(
+                StatusCode::TOO_MANY_REQUESTS,
+                "too_many_attempts",
+            ));
+        }
+        Err(LoginFailure::Locked(locked_until)) => {
+            login::register_refused(&state, addr.ip(), user.email).await;
+            return Err(ControllerError::localized_with(
+                StatusCode::LOCKED,
+                "account_locked",
+                &[("until", &locked_until.to_string())],
+            ));
+        }
         Err(_) => {
-            return Err(ControllerError {
-                message: "Usuário inválido".to_string(),
-                status_code: StatusCode::UNAUTHORIZED,
-            })
+            return Err(ControllerError::localized(
+                StatusCode::UNAUTHORIZED,
+                "invalid_credentials",
+            ))
         }
-        Ok(id) => id,
+        Ok(user) => user.id,
     };
 
     // Validate if user has access on the current time, if not return unauthorized
     let access_search = users_accesses::has_access_now(&state.db_pool, user_id).await;
     let _is_user_valid = match access_search {
         Err(_) => {
-            return Err(ControllerError {
-                message: "Usuário não tem acesso no momento".to_string(),
-                status_code: StatusCode::UNAUTHORIZED,
-            })
+            return Err(ControllerError::localized(
+                StatusCode::UNAUTHORIZED,
+                "no_access_now",
+            ))
         }
         Ok(access) => access,
     };
@@ -81,6 +134,332 @@ pub async fn unlock(
     mqtt::publish_open_door(&state.mqtt_cli).await;
 
     Ok(Json(Response {
-        message: "Porta destrancada".to_string(),
+        message: i18n::text("door_unlocked", &[]),
+    }))
+}
+
+// Doors are only those of the configuration, a door id sent by the client is not to be trusted
+pub fn ensure_known_door(state: &AppState, door_id: u32) -> Result<(), ControllerError> {
+    if state.config.doors.contains(&door_id) {
+        Ok(())
+    } else {
+        Err(ControllerError::localized(
+            StatusCode::NOT_FOUND,
+            "door_not_found",
+        ))
+    }
+}
+
+// Failures are counted per door and per client IP, the limit of either blocks the attempt
+fn ensure_not_throttled(
+    state: &AppState,
+    attempts_key: &str,
+    ip_key: &str,
+) -> Result<(), ControllerError> {
+    if state.pin_attempts.is_blocked(attempts_key) || state.keypad_attempts.is_blocked(ip_key) {
+        return Err(ControllerError::localized(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too_many_attempts",
+        ));
+    }
+
+    Ok(())
+}
+
+fn register_failure(state: &AppState, attempts_key: &str, ip_key: &str) {
+    state.pin_attempts.register_failure(attempts_key);
+    state.keypad_attempts.register_failure(ip_key);
+}
+
+// Duress codes are still looked up once the limit of failures is reached, up to this many times
+// the limit. Past it they could be guessed at and the lookup is skipped
+const DURESS_LIMIT_FACTOR: u32 = 2;
+
+fn past_duress_cap(limiter: &FailureLimiter, key: &str) -> bool {
+    limiter.failures(key) >= limiter.max_failures() * DURESS_LIMIT_FACTOR
+}
+
+#[utoipa::path(
+    post,
+    path = "/doors/{id}/unlock/pin",
+    tag = "doors",
+    params(
+        ("id" = u32, Path, description = "Id of the door"),
+    ),
+    request_body = PinAuth,
+    responses(
+        (status = 200, description = "Door unlocked", body = Response),
+        (status = 401, description = "Invalid credentials or no access at the moment", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown door", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Too many attempts", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn unlock_pin(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(door_id): Path<u32>,
+    ValidatedJson(pin_auth): ValidatedJson<PinAuth>,
+) -> Result<Json<Response>, ControllerError> {
+    ensure_known_door(&state, door_id)?;
+
+    let attempts_key = door_id.to_string();
+    let ip_key = addr.ip().to_string();
+    let pin_hash = auth::hash_pin(&state.config.pin_pepper, &pin_auth.pin);
+
+    // Duress codes are looked up before the throttling, a blocked keypad must still open the
+    // door and raise the alarm. The counters are left as they are, the next PIN typed isn't
+    // any more trusted
+    if !past_duress_cap(&state.pin_attempts, &attempts_key)
+        && !past_duress_cap(&state.keypad_attempts, &ip_key)
+    {
+        match users_duress_codes::find_user_by_code(&state.db_pool, pin_hash.clone()).await {
+            Ok(user_id) => return unlock_under_duress(&state, user_id, Some(door_id)).await,
+            Err(MappedErrors::NotFound) => {}
+            Err(_) => {
+                return Err(ControllerError::from_type(
+                    ControllerErrorType::InternalServerError,
+                ))
+            }
+        }
+    }
+
+    // Repeated failures on the same door keypad, or from the same client, block it for a while.
+    // A blocked attempt was still a miss on the duress codes, so it counts too
+    if let Err(err) = ensure_not_throttled(&state, &attempts_key, &ip_key) {
+        register_failure(&state, &attempts_key, &ip_key);
+        return Err(err);
+    }
+
+    let user_id = match users_pins::find_user_by_pin(&state.db_pool, pin_hash).await {
+        Ok(id) => id,
+        Err(MappedErrors::NotFound) => {
+            register_failure(&state, &attempts_key, &ip_key);
+            return Err(ControllerError::localized(
+                StatusCode::UNAUTHORIZED,
+                "invalid_pin",
+            ));
+        }
+        Err(_) => {
+            return Err(ControllerError::from_type(
+                ControllerErrorType::InternalServerError,
+            ))
+        }
+    };
+    state.pin_attempts.reset(&attempts_key);
+
+    // Same schedule rules as the password unlock
+    if users_accesses::has_access_now(&state.db_pool, user_id)
+        .await
+        .is_err()
+    {
+        return Err(ControllerError::localized(
+            StatusCode::UNAUTHORIZED,
+            "no_access_now",
+        ));
+    }
+
+    log::info!("Door {} unlocked by PIN of user {}", door_id, user_id);
+    mqtt::publish_open_door(&state.mqtt_cli).await;
+
+    Ok(Json(Response {
+        message: i18n::text("door_unlocked", &[]),
+    }))
+}
+
+// The door opens regardless of the schedule and the response is the same as a regular unlock,
+// whoever is forcing the user can't tell that an alarm was raised
+async fn unlock_under_duress(
+    state: &AppState,
+    user_id: i32,
+    door_id: Option<u32>,
+) -> Result<Json<Response>, ControllerError> {
+    mqtt::publish_duress_alarm(&state.mqtt_cli, user_id, door_id).await;
+
+    let action = match door_id {
+        Some(door_id) => format!("Duress code used at door {}", door_id),
+        None => "Duress code used at the password unlock".to_string(),
+    };
+    if let Err(err) = user_log::create(
+        &state.db_pool,
+        Some(user_id),
+        None,
+        action,
+        Severity::Critical,
+    )
+    .await
+    {
+        log::error!("Error writing audit entry: {}", err);
+    }
+
+    mqtt::publish_open_door(&state.mqtt_cli).await;
+
+    Ok(Json(Response {
+        message: i18n::text("door_unlocked", &[]),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/validate-badge",
+    tag = "doors",
+    request_body = BadgeAuth,
+    responses(
+        (status = 200, description = "Door unlocked", body = Response),
+        (status = 401, description = "Invalid credentials or no access at the moment", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown door", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Too many attempts", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn unlock_badge(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    ValidatedJson(badge_auth): ValidatedJson<BadgeAuth>,
+) -> Result<Json<Response>, ControllerError> {
+    ensure_known_door(&state, badge_auth.door_id)?;
+
+    // The reader of a door shares its failure counters with the keypad
+    let attempts_key = badge_auth.door_id.to_string();
+    let ip_key = addr.ip().to_string();
+    ensure_not_throttled(&state, &attempts_key, &ip_key)?;
+
+    let badge_uid = credentials::normalize_uid(&badge_auth.badge_uid);
+
+    let user_id = match credentials::find_user_by_badge(&state.db_pool, badge_uid.clone()).await {
+        Ok(id) => id,
+        Err(MappedErrors::NotFound) => {
+            // Keep track of lost or revoked cards that are still being used
+            if let Ok(credential) = credentials::find_by_uid(&state.db_pool, badge_uid).await {
+                let action = format!(
+                    "Badge #{} ({}) presented at door {}",
+                    credential.id, credential.status, badge_auth.door_id
+                );
+                if let Err(err) = user_log::create(
+                    &state.db_pool,
+                    Some(credential.user_id),
+                    None,
+                    action,
+                    Severity::Warning,
+                )
+                .await
+                {
+                    log::error!("Error writing audit entry: {}", err);
+                }
+            }
+
+            register_failure(&state, &attempts_key, &ip_key);
+            return Err(ControllerError::localized(
+                StatusCode::UNAUTHORIZED,
+                "invalid_badge",
+            ));
+        }
+        Err(_) => {
+            return Err(ControllerError::from_type(
+                ControllerErrorType::InternalServerError,
+            ))
+        }
+    };
+
+    state.pin_attempts.reset(&attempts_key);
+
+    if users_accesses::has_access_now(&state.db_pool, user_id)
+        .await
+        .is_err()
+    {
+        return Err(ControllerError::localized(
+            StatusCode::UNAUTHORIZED,
+            "no_access_now",
+        ));
+    }
+
+    log::info!(
+        "Door {} unlocked by badge of user {}",
+        badge_auth.door_id,
+        user_id
+    );
+    mqtt::publish_open_door(&state.mqtt_cli).await;
+
+    Ok(Json(Response {
+        message: i18n::text("door_unlocked", &[]),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/doors/{id}/unlock/visit",
+    tag = "doors",
+    params(
+        ("id" = u32, Path, description = "Id of the door"),
+    ),
+    request_body = VisitAuth,
+    responses(
+        (status = 200, description = "Door unlocked", body = Response),
+        (status = 401, description = "Invalid credentials or no access at the moment", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Unknown door", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Too many attempts", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn unlock_visit(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(door_id): Path<u32>,
+    ValidatedJson(visit_auth): ValidatedJson<VisitAuth>,
+) -> Result<Json<Response>, ControllerError> {
+    ensure_known_door(&state, door_id)?;
+
+    // Visit codes are typed on the same keypad as the PINs, so they share the failure counters
+    let attempts_key = door_id.to_string();
+    let ip_key = addr.ip().to_string();
+    ensure_not_throttled(&state, &attempts_key, &ip_key)?;
+
+    let code_hash = auth::hash_pin(&state.config.pin_pepper, &visit_auth.code);
+    let now = Utc::now().with_timezone(&Brazil::East).naive_local();
+
+    let visit = match visits::use_code(&state.db_pool, code_hash, door_id, now).await {
+        Ok(visit) => visit,
+        Err(MappedErrors::NotFound) => {
+            register_failure(&state, &attempts_key, &ip_key);
+            return Err(ControllerError::localized(
+                StatusCode::UNAUTHORIZED,
+                "invalid_visit_code",
+            ));
+        }
+        Err(_) => {
+            return Err(ControllerError::from_type(
+                ControllerErrorType::InternalServerError,
+            ))
+        }
+    };
+    state.pin_attempts.reset(&attempts_key);
+
+    let action = format!(
+        "Visit #{} ('{}') used at door {} ({}/{})",
+        visit.id,
+        visit.guest_name,
+        door_id,
+        visit.uses + 1,
+        visit.max_uses
+    );
+    if let Err(err) = user_log::create(
+        &state.db_pool,
+        Some(visit.host_user_id),
+        None,
+        action,
+        Severity::Info,
+    )
+    .await
+    {
+        log::error!("Error writing audit entry: {}", err);
+    }
+
+    mqtt::publish_open_door(&state.mqtt_cli).await;
+
+    Ok(Json(Response {
+        message: i18n::text("door_unlocked", &[]),
     }))
 }
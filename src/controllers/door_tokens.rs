@@ -0,0 +1,148 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_macros::debug_handler;
+use image::{ImageFormat, Luma};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Cursor;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::auth::door_token::{self, DoorTokenKeys, DOOR_TOKEN_AUDIENCE};
+use crate::models::{user, users_accesses};
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+use crate::AppState;
+
+#[derive(Deserialize, Default, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QrQuery {
+    #[serde(default)]
+    format: QrFormat,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PublicKeyResponse {
+    alg: &'static str,
+    aud: &'static str,
+    public_key_pem: String,
+    #[schema(value_type = Object)]
+    jwk: Value,
+}
+
+fn door_token_keys(app_state: &AppState) -> Result<&DoorTokenKeys, ControllerError> {
+    app_state.door_token_keys.as_deref().ok_or_else(|| {
+        ControllerError::localized(StatusCode::SERVICE_UNAVAILABLE, "door_tokens_disabled")
+    })
+}
+
+fn render_qr_code(token: &str, format: QrFormat) -> Result<Response, ControllerError> {
+    let code = QrCode::new(token.as_bytes())
+        .map_err(|_| ControllerError::from_type(ControllerErrorType::InternalServerError))?;
+
+    match format {
+        QrFormat::Svg => {
+            let image = code.render::<svg::Color>().min_dimensions(256, 256).build();
+
+            Ok(([(header::CONTENT_TYPE, "image/svg+xml")], image).into_response())
+        }
+        QrFormat::Png => {
+            let image = code.render::<Luma<u8>>().min_dimensions(256, 256).build();
+
+            let mut bytes = Cursor::new(Vec::new());
+            image.write_to(&mut bytes, ImageFormat::Png).map_err(|_| {
+                ControllerError::from_type(ControllerErrorType::InternalServerError)
+            })?;
+
+            Ok(([(header::CONTENT_TYPE, "image/png")], bytes.into_inner()).into_response())
+        }
+    }
+}
+
+// Issues a door token for the user and renders it as a QR code, the schedule is checked here
+// since the door only verifies the signature, the door id and the expiry
+#[utoipa::path(
+    get,
+    path = "/user/{user_id}/door/{door_id}/qr",
+    tag = "doors",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+        ("door_id" = u32, Path, description = "Id of the door"),
+        QrQuery,
+    ),
+    responses(
+        (status = 200, description = "QR code of the door token", content(
+            ("image/svg+xml" = String),
+            ("image/png" = Vec<u8>),
+        )),
+        (status = 403, description = "User has no access at the moment", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 503, description = "Door tokens are not configured", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn qr_code(
+    State(app_state): State<AppState>,
+    Path((user_id, door_id)): Path<(u32, u32)>,
+    Query(query): Query<QrQuery>,
+) -> Result<Response, ControllerError> {
+    let keys = door_token_keys(&app_state)?;
+
+    let user = user::find(&app_state.db_pool, user_id).await?;
+
+    if !user.is_active
+        || users_accesses::has_access_now(&app_state.db_pool, user.id)
+            .await
+            .is_err()
+    {
+        return Err(ControllerError::localized(
+            StatusCode::FORBIDDEN,
+            "no_access_now",
+        ));
+    }
+
+    let token = door_token::generate_door_token(keys, user.id, door_id)?;
+
+    render_qr_code(&token, query.format)
+}
+
+// Public key the door controllers use to verify the tokens offline
+#[utoipa::path(
+    get,
+    path = "/doors/token-key",
+    tag = "doors",
+    responses(
+        (status = 200, description = "Key the doors verify the tokens with", body = PublicKeyResponse),
+        (status = 503, description = "Door tokens are not configured", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn public_key(
+    State(app_state): State<AppState>,
+) -> Result<Json<PublicKeyResponse>, ControllerError> {
+    let keys = door_token_keys(&app_state)?;
+
+    Ok(Json(PublicKeyResponse {
+        alg: "EdDSA",
+        aud: DOOR_TOKEN_AUDIENCE,
+        public_key_pem: keys.public_key_pem().to_string(),
+        jwk: json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "alg": "EdDSA",
+            "use": "sig",
+            "x": keys.public_key_x(),
+        }),
+    }))
+}
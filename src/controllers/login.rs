@@ -0,0 +1,196 @@
+use chrono::{Duration, NaiveDateTime, Utc};
+use log::{error, warn};
+use std::net::IpAddr;
+
+use crate::models::login_lockouts;
+use crate::models::user::{self, User};
+use crate::models::user_log::{self, Severity};
+use crate::utils::MappedErrors;
+use crate::AppState;
+
+// Password checks shared by /login and /validate-password. Failures are counted per account
+// and client IP, the account gets locked for that IP for a while after too many. Guesses spread
+// over many IPs are caught by the counter of the account, which locks it everywhere past a
+// higher limit, and per client IP alone through `login_attempts`
+const MAX_FAILED_LOGINS: i32 = 5;
+const MAX_ACCOUNT_FAILED_LOGINS: i32 = 20;
+const LOCKOUT_MINUTES: i64 = 15;
+
+// Each failure in a row doubles the wait before answering, up to the cap
+const BASE_DELAY_MS: u64 = 250;
+const MAX_DELAY_MS: u64 = 4000;
+
+pub enum LoginFailure {
+    Throttled,
+    Locked(NaiveDateTime),
+    InvalidCredentials,
+    InternalServerError,
+}
+
+fn failure_delay(failures: u32) -> std::time::Duration {
+    let factor = 1u64 << failures.saturating_sub(1).min(16);
+
+    std::time::Duration::from_millis((BASE_DELAY_MS * factor).min(MAX_DELAY_MS))
+}
+
+async fn register_failure(app_state: &AppState, ip: IpAddr, account: Option<User>) -> u32 {
+    let ip_key = ip.to_string();
+    app_state.login_attempts.register_failure(&ip_key);
+    let mut failures = app_state.login_attempts.failures(&ip_key);
+
+    let account = match account {
+        Some(account) => account,
+        None => return failures,
+    };
+
+    let lock_until = Utc::now().naive_utc() + Duration::minutes(LOCKOUT_MINUTES);
+    match login_lockouts::register_failure(
+        &app_state.db_pool,
+        account.id,
+        ip_key,
+        MAX_FAILED_LOGINS,
+        lock_until,
+    )
+    .await
+    {
+        Ok((attempts, is_locked)) => {
+            failures = failures.max(attempts as u32);
+
+            if is_locked {
+                warn!(
+                    "User {} locked for {} after {} failed logins",
+                    account.id, ip, attempts
+                );
+
+                let action = format!(
+                    "Account locked for {} until {} after {} failed logins",
+                    ip, lock_until, attempts
+                );
+                if let Err(err) = user_log::create(
+                    &app_state.db_pool,
+                    Some(account.id),
+                    None,
+                    action,
+                    Severity::Warning,
+                )
+                .await
+                {
+                    error!("Error writing audit entry: {}", err);
+                }
+            }
+        }
+        Err(err) => error!(
+            "Error counting failed login of user {}: {}",
+            account.id, err
+        ),
+    }
+
+    match user::register_failed_login(
+        &app_state.db_pool,
+        account.id,
+        MAX_ACCOUNT_FAILED_LOGINS,
+        lock_until,
+    )
+    .await
+    {
+        Ok((attempts, is_locked)) => {
+            if is_locked {
+                warn!(
+                    "User {} locked after {} failed logins",
+                    account.id, attempts
+                );
+
+                let action = format!(
+                    "Account locked until {} after {} failed logins, last from {}",
+                    lock_until, attempts, ip
+                );
+                if let Err(err) = user_log::create(
+                    &app_state.db_pool,
+                    Some(account.id),
+                    None,
+                    action,
+                    Severity::Warning,
+                )
+                .await
+                {
+                    error!("Error writing audit entry: {}", err);
+                }
+            }
+        }
+        Err(err) => error!(
+            "Error counting failed login of user {}: {}",
+            account.id, err
+        ),
+    }
+
+    failures
+}
+
+// Counts a password refused without being checked, because of the throttling or the lock.
+// Callers that look the password up elsewhere first, like the duress codes, can't have their
+// misses go uncounted
+pub async fn register_refused(app_state: &AppState, ip: IpAddr, email: String) {
+    let account = user::find_by_email(&app_state.db_pool, email).await.ok();
+
+    register_failure(app_state, ip, account).await;
+}
+
+pub async fn attempt_login(
+    app_state: &AppState,
+    ip: IpAddr,
+    email: String,
+    password: String,
+) -> Result<User, LoginFailure> {
+    if app_state.login_attempts.is_blocked(&ip.to_string()) {
+        return Err(LoginFailure::Throttled);
+    }
+
+    // The lock is checked before the password, a locked account can't be guessed at
+    let account = match user::find_by_email(&app_state.db_pool, email.clone()).await {
+        Ok(account) => Some(account),
+        Err(MappedErrors::NotFound) => None,
+        Err(_) => return Err(LoginFailure::InternalServerError),
+    };
+
+    if let Some(account) = &account {
+        if let Some(locked_until) = account.locked_until {
+            if locked_until > Utc::now().naive_utc() {
+                return Err(LoginFailure::Locked(locked_until));
+            }
+        }
+
+        let locked_until =
+            login_lockouts::find_locked_until(&app_state.db_pool, account.id, ip.to_string())
+                .await
+                .map_err(|_| LoginFailure::InternalServerError)?;
+
+        if let Some(locked_until) = locked_until {
+            if locked_until > Utc::now().naive_utc() {
+                return Err(LoginFailure::Locked(locked_until));
+            }
+        }
+    }
+
+    match user::find_by_login_user(&app_state.db_pool, email, password).await {
+        Ok(user) => {
+            login_lockouts::clear(&app_state.db_pool, user.id, ip.to_string())
+                .await
+                .map_err(|_| LoginFailure::InternalServerError)?;
+
+            if user.failed_login_attempts > 0 || user.locked_until.is_some() {
+                user::unlock(&app_state.db_pool, user.id)
+                    .await
+                    .map_err(|_| LoginFailure::InternalServerError)?;
+            }
+
+            Ok(user)
+        }
+        Err(MappedErrors::NotFound) => {
+            let failures = register_failure(app_state, ip, account).await;
+            tokio::time::sleep(failure_delay(failures)).await;
+
+            Err(LoginFailure::InvalidCredentials)
+        }
+        Err(_) => Err(LoginFailure::InternalServerError),
+    }
+}
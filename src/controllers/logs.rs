@@ -0,0 +1,37 @@
+use axum::extract::{Json, Query, State};
+use axum_macros::debug_handler;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::user_log::{self, LogFilter, UserLog};
+use crate::utils::errors::ControllerError;
+use crate::AppState;
+
+#[derive(Serialize, ToSchema)]
+pub struct LogsListResponse {
+    logs: Vec<UserLog>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/logs",
+    operation_id = "list_logs",
+    tag = "logs",
+    params(
+        LogFilter,
+    ),
+    responses(
+        (status = 200, description = "Audit log entries", body = LogsListResponse),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn list_all(
+    State(app_state): State<AppState>,
+    Query(filter): Query<LogFilter>,
+) -> Result<Json<LogsListResponse>, ControllerError> {
+    let logs = user_log::list(&app_state.db_pool, filter).await?;
+
+    Ok(Json(LogsListResponse { logs }))
+}
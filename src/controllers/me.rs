@@ -0,0 +1,148 @@
+use axum::extract::{Json, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::auth::CurrentUser;
+use crate::controllers::password_policy;
+use crate::models::user;
+use crate::models::user_log::{self, Severity};
+use crate::models::users_accesses::{self, UserAccess};
+use crate::utils::errors::{ControllerError, ControllerErrorType, PasswordError};
+use crate::utils::validation::ValidatedJson;
+use crate::utils::MappedErrors;
+use crate::AppState;
+
+// Self-service routes, always scoped to the user of the token
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MyAccessesResponse {
+    accesses: Vec<UserAccess>,
+}
+
+fn user_id(current_user: &CurrentUser) -> u32 {
+    current_user.user_id as u32
+}
+
+#[utoipa::path(
+    get,
+    path = "/me",
+    tag = "me",
+    responses(
+        (status = 200, description = "The logged in user", body = ListUser),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn find_me(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Json<user::ListUser>, ControllerError> {
+    let user = user::find(&app_state.db_pool, user_id(&current_user))
+        .await
+        .map_err(|err| match err {
+            // The token outlived the user
+            MappedErrors::NotFound => ControllerError::from_type(ControllerErrorType::Unauthorized),
+            _ => ControllerError::from(err),
+        })?;
+
+    Ok(Json(user))
+}
+
+#[utoipa::path(
+    put,
+    path = "/me",
+    tag = "me",
+    request_body = UpdateUser,
+    responses(
+        (status = 204, description = "User updated"),
+        (status = 409, description = "Already exists", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn update_me(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+    user_data: ValidatedJson<user::UpdateUser>,
+) -> Result<StatusCode, ControllerError> {
+    user::update(&app_state.db_pool, user_id(&current_user), user_data.0).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/me/password",
+    tag = "me",
+    request_body = ChangePassword,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 403, description = "Current password is invalid", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn change_password(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+    password_data: ValidatedJson<user::ChangePassword>,
+) -> Result<StatusCode, PasswordError> {
+    let is_valid = user::check_password(
+        &app_state.db_pool,
+        user_id(&current_user),
+        password_data.current_password.clone(),
+    )
+    .await?;
+
+    if !is_valid {
+        return Err(
+            ControllerError::localized(StatusCode::FORBIDDEN, "invalid_current_password").into(),
+        );
+    }
+
+    let new_password = password_data.0.new_password;
+    password_policy::enforce(&app_state, Some(current_user.user_id), &new_password).await?;
+
+    user::update_password(
+        &app_state.db_pool,
+        user_id(&current_user),
+        new_password.clone(),
+    )
+    .await?;
+
+    password_policy::remember(&app_state, current_user.user_id, &new_password).await?;
+
+    user_log::create(
+        &app_state.db_pool,
+        Some(current_user.user_id),
+        Some(current_user.user_id),
+        "Password changed".to_string(),
+        Severity::Info,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/me/accesses",
+    tag = "me",
+    responses(
+        (status = 200, description = "Access windows of the logged in user", body = MyAccessesResponse),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn find_my_accesses(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Json<MyAccessesResponse>, ControllerError> {
+    let accesses = users_accesses::find(&app_state.db_pool, user_id(&current_user)).await?;
+
+    Ok(Json(MyAccessesResponse { accesses }))
+}
@@ -0,0 +1,232 @@
+use axum::extract::{Query, State};
+use axum::http::header::{COOKIE, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use axum::response::Redirect;
+use axum::Json;
+use axum_macros::debug_handler;
+use log::{info, warn};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::auth::oidc::{IdTokenClaims, OidcClient, OidcError, PENDING_LOGIN_COOKIE};
+use crate::controllers::auth::{complete_login, LoginResult};
+use crate::models::user::{self, CreateUser};
+use crate::models::user_log::{self, Severity};
+use crate::utils::errors::ControllerError;
+use crate::utils::MappedErrors;
+use crate::{auth, AppState};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+fn oidc_client(app_state: &AppState) -> Result<&OidcClient, ControllerError> {
+    app_state
+        .oidc
+        .as_deref()
+        .ok_or_else(|| ControllerError::localized(StatusCode::SERVICE_UNAVAILABLE, "oidc_disabled"))
+}
+
+impl From<OidcError> for ControllerError {
+    fn from(err: OidcError) -> Self {
+        let (status_code, code) = match err {
+            OidcError::Provider(_) => (StatusCode::BAD_GATEWAY, "oidc_provider_error"),
+            OidcError::UnknownState => (StatusCode::UNAUTHORIZED, "oidc_invalid_state"),
+            OidcError::InvalidIdToken => (StatusCode::UNAUTHORIZED, "oidc_invalid_id_token"),
+            OidcError::EmailNotVerified => (StatusCode::UNAUTHORIZED, "oidc_email_not_verified"),
+        };
+
+        match err {
+            OidcError::Provider(reason) => {
+                ControllerError::localized_with(status_code, code, &[("reason", &reason)])
+            }
+            _ => ControllerError::localized(status_code, code),
+        }
+    }
+}
+
+fn unknown_user() -> ControllerError {
+    ControllerError::localized(StatusCode::UNAUTHORIZED, "invalid_credentials")
+}
+
+// Tries for a free username before giving up on creating the user
+const USERNAME_ATTEMPTS: usize = 5;
+
+// The preferred username when it fits, the local part of the email otherwise
+fn username_for(claims: &IdTokenClaims, email: &str) -> String {
+    claims
+        .preferred_username
+        .clone()
+        .filter(|username| (3..=50).contains(&username.chars().count()))
+        .unwrap_or_else(|| email.split('@').next().unwrap_or(email).to_string())
+}
+
+// Usernames are unique, the next attempts get a random suffix and still fit in 50 chars
+fn username_attempt(username: &str, attempt: usize) -> String {
+    if attempt == 0 {
+        return username.to_string();
+    }
+
+    let base: String = username.chars().take(41).collect();
+    format!("{}-{}", base, auth::generate_random_token(4))
+}
+
+fn pending_login_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            cookie
+                .trim()
+                .strip_prefix(PENDING_LOGIN_COOKIE)?
+                .strip_prefix('=')
+        })
+}
+
+// Maps the verified email to a user, creating it when allowed. Disabled users are refused.
+// Returns the id, email and if the user is an admin
+async fn resolve_user(
+    app_state: &AppState,
+    oidc: &OidcClient,
+    claims: &IdTokenClaims,
+    email: String,
+) -> Result<(i32, String, bool), ControllerError> {
+    match user::find_by_email(&app_state.db_pool, email.clone()).await {
+        Ok(user) if user.is_active => return Ok((user.id, user.email, user.is_admin)),
+        Ok(user) => {
+            warn!("OIDC login of disabled user {}", user.id);
+            return Err(unknown_user());
+        }
+        Err(MappedErrors::NotFound) if oidc.create_users() => {}
+        Err(MappedErrors::NotFound) => {
+            warn!("OIDC login of unknown email {}", email);
+            return Err(unknown_user());
+        }
+        Err(err) => return Err(ControllerError::from(err)),
+    }
+
+    // The random password can't be used, the user signs in through the provider. A conflict
+    // on the email means it was created meanwhile, and it is refused like any other conflict
+    let username = username_for(claims, &email);
+    let mut attempt = 0;
+    let user_id = loop {
+        let created = user::create(
+            &app_state.db_pool,
+            CreateUser {
+                username: username_attempt(&username, attempt),
+                password: auth::generate_random_token(32),
+                email: email.clone(),
+            },
+        )
+        .await;
+
+        match created {
+            Err(MappedErrors::Conflict) if attempt + 1 < USERNAME_ATTEMPTS => attempt += 1,
+            result => break result?,
+        }
+    };
+
+    info!("User {} created on first OIDC login", user_id);
+    audit(
+        app_state,
+        user_id,
+        format!("User '{}' created by OIDC login", email),
+    )
+    .await?;
+
+    Ok((user_id, email, false))
+}
+
+async fn audit(app_state: &AppState, user_id: i32, action: String) -> Result<(), ControllerError> {
+    user_log::create(
+        &app_state.db_pool,
+        Some(user_id),
+        Some(user_id),
+        action,
+        Severity::Info,
+    )
+    .await
+    .map_err(ControllerError::from)
+}
+
+// Sends the user to the identity provider
+#[utoipa::path(
+    get,
+    path = "/login/oidc",
+    tag = "auth",
+    responses(
+        (status = 303, description = "Redirect to the identity provider, with the pending login cookie"),
+        (status = 503, description = "OIDC login is not configured", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn start_login(
+    State(app_state): State<AppState>,
+) -> Result<([(HeaderName, String); 1], Redirect), ControllerError> {
+    let oidc = oidc_client(&app_state)?;
+    let (url, cookie) = oidc.authorization_url().await?;
+
+    Ok((
+        [(SET_COOKIE, oidc.pending_login_cookie(&cookie))],
+        Redirect::to(&url),
+    ))
+}
+
+// Redirect URI registered on the provider, ends like the password login: with the session,
+// or with the MFA challenge when TOTP is needed
+#[utoipa::path(
+    get,
+    path = "/login/oidc/callback",
+    tag = "auth",
+    params(
+        CallbackQuery,
+    ),
+    responses(
+        (status = 200, description = "Session tokens, or an MFA challenge when TOTP is enabled", body = LoginResult),
+        (status = 401, description = "Unknown state, login started on another browser or invalid ID token", body = Problem, content_type = "application/problem+json"),
+        (status = 502, description = "Identity provider error", body = Problem, content_type = "application/problem+json"),
+        (status = 503, description = "Not configured", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn callback(
+    State(app_state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<CallbackQuery>,
+) -> Result<([(HeaderName, String); 1], Json<LoginResult>), ControllerError> {
+    let oidc = oidc_client(&app_state)?;
+
+    if let Some(error) = query.error {
+        warn!(
+            "OIDC login refused by the provider: {} {}",
+            error,
+            query.error_description.unwrap_or_default()
+        );
+        return Err(unknown_user());
+    }
+
+    let (code, state) = match (query.code, query.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(OidcError::UnknownState.into()),
+    };
+
+    let claims = oidc
+        .finish_login(&code, &state, pending_login_cookie(&headers))
+        .await?;
+    let email = claims.email.clone().ok_or(OidcError::EmailNotVerified)?;
+
+    let (user_id, email, is_admin) = resolve_user(&app_state, oidc, &claims, email).await?;
+
+    audit(&app_state, user_id, "Login with OIDC".to_string()).await?;
+
+    let result = complete_login(&app_state, user_id, email, is_admin).await?;
+
+    Ok(([(SET_COOKIE, oidc.pending_login_cookie(""))], Json(result)))
+}
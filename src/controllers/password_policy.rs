@@ -0,0 +1,108 @@
+use axum::extract::{Json, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+
+use crate::auth::password_policy;
+use crate::auth::Principal;
+use crate::models::password_history;
+use crate::models::password_policy::{self as policy_model, PasswordPolicy, PasswordPolicyUpdate};
+use crate::models::user;
+use crate::models::user_log::{self, Severity};
+use crate::utils::errors::{ControllerError, PasswordError};
+use crate::utils::validation::ValidatedJson;
+use crate::{auth, AppState};
+
+// Checks a new password against the policy, and against the user's previous passwords
+// when it is for an existing user. The current password is one of them even when it was set
+// before the history was kept
+pub(crate) async fn enforce(
+    app_state: &AppState,
+    user_id: Option<i32>,
+    password: &str,
+) -> Result<(), PasswordError> {
+    let policy = policy_model::find(&app_state.db_pool).await?;
+
+    let mut violations = password_policy::check(&policy, password);
+
+    if let (Some(user_id), true) = (user_id, policy.history_size > 0) {
+        let recent =
+            password_history::find_recent(&app_state.db_pool, user_id, policy.history_size as i64)
+                .await?;
+
+        let is_current =
+            user::check_password(&app_state.db_pool, user_id as u32, password.to_string()).await?;
+
+        if is_current || auth::matches_any_password(password.to_string(), recent).await {
+            violations.push(password_policy::reuse_violation(policy.history_size));
+        }
+    }
+
+    if !violations.is_empty() {
+        return Err(PasswordError::PolicyViolations(violations));
+    }
+
+    Ok(())
+}
+
+// Adds the password to the user's history, call it whenever a password is set
+pub(crate) async fn remember(
+    app_state: &AppState,
+    user_id: i32,
+    password: &str,
+) -> Result<(), ControllerError> {
+    let password_hash = auth::hash_password(password.to_string()).await?;
+
+    password_history::create(&app_state.db_pool, user_id, password_hash)
+        .await
+        .map_err(ControllerError::from)
+}
+
+#[utoipa::path(
+    get,
+    path = "/password-policy",
+    tag = "settings",
+    responses(
+        (status = 200, description = "Current policy", body = PasswordPolicy),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn find_policy(
+    State(app_state): State<AppState>,
+) -> Result<Json<PasswordPolicy>, ControllerError> {
+    let policy = policy_model::find(&app_state.db_pool).await?;
+
+    Ok(Json(policy))
+}
+
+// Existing passwords aren't checked again, the new rules apply from their next change
+#[utoipa::path(
+    put,
+    path = "/password-policy",
+    tag = "settings",
+    request_body = PasswordPolicyUpdate,
+    responses(
+        (status = 204, description = "Policy updated"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn update_policy(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    policy_data: ValidatedJson<PasswordPolicyUpdate>,
+) -> Result<StatusCode, ControllerError> {
+    policy_model::update(&app_state.db_pool, policy_data.0).await?;
+
+    user_log::create(
+        &app_state.db_pool,
+        None,
+        principal.user_id(),
+        "Password policy updated".to_string(),
+        Severity::Info,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,215 @@
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::{Duration, Utc};
+use log::{error, warn};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::controllers::auth::revoke_all_sessions;
+use crate::controllers::password_policy;
+use crate::models::password_reset_tokens::{self, PasswordResetTokenCreate};
+use crate::models::user;
+use crate::models::user_log::{self, Severity};
+use crate::services::mailer::Mail;
+use crate::utils::errors::{ControllerError, PasswordError};
+use crate::utils::validation::ValidatedJson;
+use crate::utils::MappedErrors;
+use crate::{auth, AppState};
+
+const RESET_TOKEN_TTL_MINUTES: i64 = 30;
+
+// At most 3 reset mails per user per hour
+const MAX_RESET_REQUESTS: i64 = 3;
+const RESET_REQUESTS_WINDOW_MINUTES: i64 = 60;
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct ResetRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct ResetConfirm {
+    #[validate(length(min = 1))]
+    pub token: String,
+    // Checked against the password policy instead
+    pub new_password: String,
+}
+
+fn invalid_reset_token() -> ControllerError {
+    ControllerError::localized(StatusCode::BAD_REQUEST, "invalid_reset_token")
+}
+
+// With GCA_PASSWORD_RESET_URL set the mail links to the frontend page, e.g.
+// https://app/reset?token=..., otherwise only the token is sent
+fn reset_mail(reset_url: Option<&str>, to: String, token: &str) -> Mail {
+    let instructions = match reset_url {
+        Some(url) => format!(
+            "Open the link below to choose a new password:\n\n{}?token={}",
+            url, token
+        ),
+        None => format!("Use the token below to choose a new password:\n\n{}", token),
+    };
+
+    Mail {
+        to,
+        subject: "Password reset".to_string(),
+        body: format!(
+            "{}\n\nIt expires in {} minutes. If you didn't ask for it, ignore this mail.\n",
+            instructions, RESET_TOKEN_TTL_MINUTES
+        ),
+    }
+}
+
+// Always answers 202 right away, the mail is sent in the background. Neither the status nor
+// the time taken tell which emails are registered
+#[utoipa::path(
+    post,
+    path = "/password-reset/request",
+    tag = "auth",
+    request_body = ResetRequest,
+    responses(
+        (status = 202, description = "Reset email sent when the account exists"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn request_reset(
+    State(app_state): State<AppState>,
+    reset_data: ValidatedJson<ResetRequest>,
+) -> StatusCode {
+    tokio::spawn(send_reset_mail(app_state, reset_data.0.email));
+
+    StatusCode::ACCEPTED
+}
+
+async fn send_reset_mail(app_state: AppState, email: String) {
+    let user = match user::find_active_by_email(&app_state.db_pool, email).await {
+        Ok(user) => user,
+        Err(MappedErrors::NotFound) => return,
+        Err(err) => {
+            error!("Unable to find the user of a password reset: {}", err);
+            return;
+        }
+    };
+
+    let now = Utc::now().naive_utc();
+    let recent_requests = match password_reset_tokens::count_since(
+        &app_state.db_pool,
+        user.id,
+        now - Duration::minutes(RESET_REQUESTS_WINDOW_MINUTES),
+    )
+    .await
+    {
+        Ok(count) => count,
+        Err(err) => {
+            error!(
+                "Unable to count password resets of user {}: {}",
+                user.id, err
+            );
+            return;
+        }
+    };
+
+    if recent_requests >= MAX_RESET_REQUESTS {
+        warn!("Too many password reset requests for user {}", user.id);
+        return;
+    }
+
+    let token = auth::generate_random_token(32);
+
+    if let Err(err) = password_reset_tokens::create(
+        &app_state.db_pool,
+        PasswordResetTokenCreate {
+            user_id: user.id,
+            token_hash: auth::hash_token(&token),
+            expires_at: now + Duration::minutes(RESET_TOKEN_TTL_MINUTES),
+        },
+    )
+    .await
+    {
+        error!(
+            "Unable to create password reset token of user {}: {}",
+            user.id, err
+        );
+        return;
+    }
+
+    if let Err(err) = app_state
+        .mailer
+        .send(reset_mail(
+            app_state.config.password_reset_url.as_deref(),
+            user.email,
+            &token,
+        ))
+        .await
+    {
+        error!("Unable to send password reset mail: {}", err);
+    }
+}
+
+// Sets the new password and ends every session of the user
+#[utoipa::path(
+    post,
+    path = "/password-reset/confirm",
+    tag = "auth",
+    request_body = ResetConfirm,
+    responses(
+        (status = 204, description = "Password changed"),
+        (status = 400, description = "Invalid or expired reset token", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn confirm_reset(
+    State(app_state): State<AppState>,
+    confirm_data: ValidatedJson<ResetConfirm>,
+) -> Result<StatusCode, PasswordError> {
+    let token_hash = auth::hash_token(&confirm_data.token);
+
+    let stored = password_reset_tokens::find_by_hash(&app_state.db_pool, token_hash)
+        .await
+        .map_err(|err| match err {
+            MappedErrors::NotFound => invalid_reset_token(),
+            _ => ControllerError::from(err),
+        })?;
+
+    if stored.used_at.is_some() || stored.expires_at < Utc::now().naive_utc() {
+        return Err(invalid_reset_token().into());
+    }
+
+    // Checked before using the token, so a rejected password can be fixed and sent again
+    let new_password = confirm_data.0.new_password;
+    password_policy::enforce(&app_state, Some(stored.user_id), &new_password).await?;
+
+    let is_first_use =
+        password_reset_tokens::use_token(&app_state.db_pool, stored.id, stored.user_id).await?;
+
+    if !is_first_use {
+        return Err(invalid_reset_token().into());
+    }
+
+    user::update_password(
+        &app_state.db_pool,
+        stored.user_id as u32,
+        new_password.clone(),
+    )
+    .await?;
+
+    password_policy::remember(&app_state, stored.user_id, &new_password).await?;
+
+    revoke_all_sessions(&app_state, stored.user_id).await?;
+
+    user_log::create(
+        &app_state.db_pool,
+        Some(stored.user_id),
+        None,
+        "Password reset by email".to_string(),
+        Severity::Info,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,311 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::auth::{permissions, Principal};
+use crate::models::roles::{self, Role};
+use crate::models::user;
+use crate::models::user_log::{self, Severity};
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+use crate::utils::validation::ValidatedJson;
+use crate::AppState;
+
+#[derive(Serialize, ToSchema)]
+pub struct RoleResponse {
+    id: i32,
+    name: String,
+    description: String,
+    permissions: Vec<String>,
+    created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RolesListResponse {
+    roles: Vec<Role>,
+}
+
+// Roles can only be handed out or changed by a caller that holds all their permissions
+async fn ensure_holds_role(
+    app_state: &AppState,
+    held: &[String],
+    role_id: i32,
+) -> Result<(), ControllerError> {
+    let role_permissions = roles::find_permissions(&app_state.db_pool, role_id).await?;
+
+    permissions::ensure_held(held, &role_permissions)
+}
+
+async fn audit(
+    app_state: &AppState,
+    user_id: Option<i32>,
+    principal: &Principal,
+    action: String,
+) -> Result<(), ControllerError> {
+    user_log::create(
+        &app_state.db_pool,
+        user_id,
+        principal.user_id(),
+        action,
+        Severity::Info,
+    )
+    .await
+    .map_err(ControllerError::from)
+}
+
+#[utoipa::path(
+    post,
+    path = "/roles",
+    tag = "roles",
+    request_body = RoleData,
+    responses(
+        (status = 201, description = "Role created"),
+        (status = 403, description = "Permission beyond the ones of the caller", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Already exists", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn create_role(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    role_data: ValidatedJson<roles::RoleData>,
+) -> Result<StatusCode, ControllerError> {
+    let held = principal.permissions(&app_state.db_pool).await?;
+    permissions::ensure_held(&held, &role_data.permissions)?;
+
+    let action = format!(
+        "Role '{}' created with [{}]",
+        role_data.name,
+        role_data.permissions.join(", ")
+    );
+
+    roles::create(&app_state.db_pool, role_data.0).await?;
+
+    audit(&app_state, None, &principal, action).await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[utoipa::path(
+    get,
+    path = "/roles",
+    operation_id = "list_roles",
+    tag = "roles",
+    responses(
+        (status = 200, description = "Every role", body = RolesListResponse),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn list_all(
+    State(app_state): State<AppState>,
+) -> Result<Json<RolesListResponse>, ControllerError> {
+    let roles = roles::list(&app_state.db_pool).await?;
+
+    Ok(Json(RolesListResponse { roles }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/roles/{id}",
+    tag = "roles",
+    params(
+        ("id" = u32, Path, description = "Id of the role"),
+    ),
+    responses(
+        (status = 200, description = "The role", body = RoleResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn find_role(
+    State(app_state): State<AppState>,
+    Path(role_id): Path<u32>,
+) -> Result<Json<RoleResponse>, ControllerError> {
+    let role = roles::find(&app_state.db_pool, role_id).await?;
+
+    let permissions = roles::find_permissions(&app_state.db_pool, role.id).await?;
+
+    Ok(Json(RoleResponse {
+        id: role.id,
+        name: role.name,
+        description: role.description,
+        permissions,
+        created_at: role.created_at,
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/roles/{id}",
+    tag = "roles",
+    params(
+        ("id" = u32, Path, description = "Id of the role"),
+    ),
+    request_body = RoleData,
+    responses(
+        (status = 204, description = "Role updated"),
+        (status = 403, description = "Permission beyond the ones of the caller", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Already exists", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn update_role(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path(role_id): Path<u32>,
+    role_data: ValidatedJson<roles::RoleData>,
+) -> Result<StatusCode, ControllerError> {
+    let role = roles::find(&app_state.db_pool, role_id).await?;
+
+    let held = principal.permissions(&app_state.db_pool).await?;
+    ensure_holds_role(&app_state, &held, role.id).await?;
+    permissions::ensure_held(&held, &role_data.permissions)?;
+
+    let action = format!(
+        "Role #{} updated with [{}]",
+        role_id,
+        role_data.permissions.join(", ")
+    );
+
+    roles::update(&app_state.db_pool, role_id, role_data.0).await?;
+
+    audit(&app_state, None, &principal, action).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/roles/{id}",
+    tag = "roles",
+    params(
+        ("id" = u32, Path, description = "Id of the role"),
+    ),
+    responses(
+        (status = 204, description = "Role deleted"),
+        (status = 403, description = "Permission beyond the ones of the caller", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn delete_role(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path(role_id): Path<u32>,
+) -> Result<StatusCode, ControllerError> {
+    let role = roles::find(&app_state.db_pool, role_id).await?;
+
+    let held = principal.permissions(&app_state.db_pool).await?;
+    ensure_holds_role(&app_state, &held, role.id).await?;
+
+    roles::delete(&app_state.db_pool, role_id).await?;
+
+    let action = format!("Role '{}' deleted", role.name);
+    audit(&app_state, None, &principal, action).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/{id}/roles",
+    tag = "roles",
+    params(
+        ("id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "Roles of the user", body = RolesListResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn find_user_roles(
+    State(app_state): State<AppState>,
+    Path(user_id): Path<u32>,
+) -> Result<Json<RolesListResponse>, ControllerError> {
+    user::find(&app_state.db_pool, user_id).await?;
+
+    let roles = roles::find_by_user(&app_state.db_pool, user_id as i32).await?;
+
+    Ok(Json(RolesListResponse { roles }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/{id}/roles",
+    tag = "roles",
+    params(
+        ("id" = u32, Path, description = "Id of the user"),
+    ),
+    request_body = UserRoles,
+    responses(
+        (status = 204, description = "Roles of the user replaced"),
+        (status = 403, description = "Permission beyond the ones of the caller", body = Problem, content_type = "application/problem+json"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn set_user_roles(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path(user_id): Path<u32>,
+    roles_data: ValidatedJson<roles::UserRoles>,
+) -> Result<StatusCode, ControllerError> {
+    if principal.user_id() == Some(user_id as i32) {
+        return Err(ControllerError::localized(
+            StatusCode::FORBIDDEN,
+            "self_role_change",
+        ));
+    }
+
+    user::find(&app_state.db_pool, user_id).await?;
+
+    let known = roles::list(&app_state.db_pool).await?;
+
+    let names = roles_data
+        .role_ids
+        .iter()
+        .map(|role_id| known.iter().find(|role| role.id == *role_id))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| ControllerError::from_type(ControllerErrorType::BodyParsingError))?
+        .iter()
+        .map(|role| role.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    // Both the roles granted and the ones taken away must be within the caller's permissions
+    let current = roles::find_by_user(&app_state.db_pool, user_id as i32).await?;
+    let held = principal.permissions(&app_state.db_pool).await?;
+    let granted = roles_data
+        .role_ids
+        .iter()
+        .filter(|role_id| !current.iter().any(|role| role.id == **role_id));
+    let taken = current
+        .iter()
+        .map(|role| &role.id)
+        .filter(|role_id| !roles_data.role_ids.contains(role_id));
+
+    for role_id in granted.chain(taken) {
+        ensure_holds_role(&app_state, &held, *role_id).await?;
+    }
+
+    roles::set_user_roles(&app_state.db_pool, user_id as i32, roles_data.0.role_ids).await?;
+
+    let action = format!("Roles set to [{}]", names);
+    audit(&app_state, Some(user_id as i32), &principal, action).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
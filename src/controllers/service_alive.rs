@@ -1,6 +1,14 @@
 use crate::utils::{build_response, Response};
 use axum::{http::StatusCode, Json};
 
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "service",
+    responses(
+        (status = 200, description = "The gateway is up", body = Response),
+    )
+)]
 pub async fn alive_route() -> (StatusCode, Json<Response>) {
     build_response(StatusCode::OK, "O gateway está online!!".to_string())
 }
@@ -0,0 +1,491 @@
+use axum::extract::{Json, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use log::error;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::auth::totp::{self, MfaClaims};
+use crate::auth::CurrentUser;
+use crate::controllers::auth::{issue_session, revoke_access_token, LoginResponse};
+use crate::models::user;
+use crate::models::user_log::{self, Severity};
+use crate::models::users_totp::{self, TotpCode, UserTotp};
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+use crate::utils::validation::{cross_field_error, ValidatedJson};
+use crate::utils::MappedErrors;
+use crate::{auth, AppState};
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct MfaTokenRequest {
+    #[validate(length(min = 1))]
+    pub mfa_token: String,
+}
+
+// Either a code from the authenticator or one of the recovery codes
+#[derive(Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "validate_second_factor"))]
+pub struct TotpLoginRequest {
+    #[validate(length(min = 1))]
+    pub mfa_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+fn validate_second_factor(login_data: &TotpLoginRequest) -> Result<(), ValidationError> {
+    match (&login_data.code, &login_data.recovery_code) {
+        (None, None) => Err(cross_field_error("code", "code_or_recovery_code")),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TotpLoginResponse {
+    #[serde(flatten)]
+    session: LoginResponse,
+    // Only set when the login completed the enrollment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TotpEnrollmentResponse {
+    secret: String,
+    otpauth_uri: String,
+    qr_code_svg: String,
+}
+
+// Recovery codes are only shown once, they are stored hashed
+#[derive(Serialize, ToSchema)]
+pub struct RecoveryCodesResponse {
+    recovery_codes: Vec<String>,
+}
+
+fn invalid_totp_code() -> ControllerError {
+    ControllerError::localized(StatusCode::UNAUTHORIZED, "invalid_totp_code")
+}
+
+fn attempts_key(user_id: i32) -> String {
+    format!("user:{}", user_id)
+}
+
+// MFA tokens are single-use, they are revoked once exchanged for a session
+fn mfa_claims(app_state: &AppState, mfa_token: &str) -> Result<MfaClaims, ControllerError> {
+    match totp::validate_mfa_token(&app_state.keyring.get(), mfa_token) {
+        Some(claims) if !app_state.token_denylist.is_revoked(&claims.jti) => Ok(claims),
+        _ => Err(ControllerError::localized(
+            StatusCode::UNAUTHORIZED,
+            "invalid_mfa_token",
+        )),
+    }
+}
+
+async fn find_totp(
+    app_state: &AppState,
+    user_id: i32,
+) -> Result<Option<UserTotp>, ControllerError> {
+    match users_totp::find(&app_state.db_pool, user_id).await {
+        Ok(totp) => Ok(Some(totp)),
+        Err(MappedErrors::NotFound) => Ok(None),
+        Err(err) => Err(ControllerError::from(err)),
+    }
+}
+
+async fn find_confirmed_totp(
+    app_state: &AppState,
+    user_id: i32,
+) -> Result<UserTotp, ControllerError> {
+    match find_totp(app_state, user_id).await? {
+        Some(totp) if totp.confirmed_at.is_some() => Ok(totp),
+        _ => Err(ControllerError::localized(
+            StatusCode::BAD_REQUEST,
+            "totp_not_enabled",
+        )),
+    }
+}
+
+fn render_qr_code(uri: &str) -> Result<String, ControllerError> {
+    let code = QrCode::new(uri.as_bytes())
+        .map_err(|_| ControllerError::from_type(ControllerErrorType::InternalServerError))?;
+
+    Ok(code.render::<svg::Color>().min_dimensions(256, 256).build())
+}
+
+async fn start_enrollment(
+    app_state: &AppState,
+    user_id: i32,
+    email: &str,
+) -> Result<TotpEnrollmentResponse, ControllerError> {
+    if let Some(totp) = find_totp(app_state, user_id).await? {
+        if totp.confirmed_at.is_some() {
+            return Err(ControllerError::localized(
+                StatusCode::CONFLICT,
+                "totp_already_enabled",
+            ));
+        }
+    }
+
+    let secret = totp::generate_secret();
+    let otpauth_uri = totp::build(&secret, email)
+        .map_err(|err| {
+            error!("{}", err);
+            ControllerError::from_type(ControllerErrorType::InternalServerError)
+        })?
+        .get_url();
+
+    users_totp::start_enrollment(&app_state.db_pool, user_id, secret.clone()).await?;
+
+    Ok(TotpEnrollmentResponse {
+        secret,
+        qr_code_svg: render_qr_code(&otpauth_uri)?,
+        otpauth_uri,
+    })
+}
+
+// Wrong codes are throttled per user, the same way as the door keypad PINs
+async fn check_code(
+    app_state: &AppState,
+    totp: &UserTotp,
+    email: &str,
+    code: &str,
+) -> Result<(), ControllerError> {
+    let key = attempts_key(totp.user_id);
+    if app_state.totp_attempts.is_blocked(&key) {
+        return Err(ControllerError::localized(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too_many_attempts",
+        ));
+    }
+
+    let generator = totp::build(&totp.secret, email).map_err(|err| {
+        error!("{}", err);
+        ControllerError::from_type(ControllerErrorType::InternalServerError)
+    })?;
+
+    let step = match totp::verify_code(&generator, code, totp.last_used_step) {
+        Some(step) => step,
+        None => {
+            app_state.totp_attempts.register_failure(&key);
+            return Err(invalid_totp_code());
+        }
+    };
+
+    let is_first_use = users_totp::use_step(&app_state.db_pool, totp.user_id, step).await?;
+
+    if !is_first_use {
+        return Err(invalid_totp_code());
+    }
+
+    app_state.totp_attempts.reset(&key);
+
+    Ok(())
+}
+
+async fn check_recovery_code(
+    app_state: &AppState,
+    user_id: i32,
+    recovery_code: &str,
+) -> Result<(), ControllerError> {
+    let key = attempts_key(user_id);
+    if app_state.totp_attempts.is_blocked(&key) {
+        return Err(ControllerError::localized(
+            StatusCode::TOO_MANY_REQUESTS,
+            "too_many_attempts",
+        ));
+    }
+
+    let code_hash = auth::hash_pin(
+        &app_state.config.pin_pepper,
+        &recovery_code.trim().to_lowercase(),
+    );
+    let is_valid = users_totp::use_recovery_code(&app_state.db_pool, user_id, code_hash).await?;
+
+    if !is_valid {
+        app_state.totp_attempts.register_failure(&key);
+        return Err(ControllerError::localized(
+            StatusCode::UNAUTHORIZED,
+            "invalid_recovery_code",
+        ));
+    }
+
+    app_state.totp_attempts.reset(&key);
+
+    audit(
+        app_state,
+        user_id,
+        "Recovery code used to log in",
+        Severity::Warning,
+    )
+    .await
+}
+
+fn new_recovery_codes(app_state: &AppState) -> (Vec<String>, Vec<String>) {
+    let codes = totp::generate_recovery_codes();
+    let hashes = codes
+        .iter()
+        .map(|code| auth::hash_pin(&app_state.config.pin_pepper, code))
+        .collect();
+
+    (codes, hashes)
+}
+
+async fn confirm_enrollment(
+    app_state: &AppState,
+    user_id: i32,
+) -> Result<Vec<String>, ControllerError> {
+    let (codes, hashes) = new_recovery_codes(app_state);
+
+    users_totp::confirm(&app_state.db_pool, user_id, hashes).await?;
+
+    audit(app_state, user_id, "TOTP enabled", Severity::Info).await?;
+
+    Ok(codes)
+}
+
+async fn audit(
+    app_state: &AppState,
+    user_id: i32,
+    action: &str,
+    severity: Severity,
+) -> Result<(), ControllerError> {
+    user_log::create(
+        &app_state.db_pool,
+        Some(user_id),
+        Some(user_id),
+        action.to_string(),
+        severity,
+    )
+    .await
+    .map_err(ControllerError::from)
+}
+
+// Second login step, exchanges the MFA token and a code for a session. For users still
+// enrolling the code confirms the enrollment and the recovery codes are returned
+#[utoipa::path(
+    post,
+    path = "/login/totp",
+    tag = "auth",
+    request_body = TotpLoginRequest,
+    responses(
+        (status = 200, description = "Session tokens", body = TotpLoginResponse),
+        (status = 401, description = "Invalid MFA token or code", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+        (status = 429, description = "Too many attempts", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn login_totp(
+    State(app_state): State<AppState>,
+    login_data: ValidatedJson<TotpLoginRequest>,
+) -> Result<Json<TotpLoginResponse>, ControllerError> {
+    let claims = mfa_claims(&app_state, &login_data.mfa_token)?;
+
+    let totp = find_totp(&app_state, claims.user_id)
+        .await?
+        .ok_or_else(|| {
+            ControllerError::localized(StatusCode::BAD_REQUEST, "totp_enrollment_not_started")
+        })?;
+
+    let recovery_codes = match (&login_data.code, &login_data.recovery_code) {
+        (Some(code), _) => {
+            check_code(&app_state, &totp, &claims.email, code).await?;
+
+            if totp.confirmed_at.is_none() {
+                Some(confirm_enrollment(&app_state, claims.user_id).await?)
+            } else {
+                None
+            }
+        }
+        (None, Some(recovery_code)) if totp.confirmed_at.is_some() => {
+            check_recovery_code(&app_state, claims.user_id, recovery_code).await?;
+            None
+        }
+        _ => {
+            return Err(ControllerError::from_type(
+                ControllerErrorType::BodyParsingError,
+            ))
+        }
+    };
+
+    revoke_access_token(&app_state, claims.jti, claims.exp).await?;
+
+    let session = issue_session(&app_state, claims.user_id, claims.email).await?;
+
+    Ok(Json(TotpLoginResponse {
+        session,
+        recovery_codes,
+    }))
+}
+
+// Enrollment for users that must have TOTP but can't log in without it yet
+#[utoipa::path(
+    post,
+    path = "/login/totp/enroll",
+    tag = "auth",
+    request_body = MfaTokenRequest,
+    responses(
+        (status = 200, description = "TOTP secret to confirm", body = TotpEnrollmentResponse),
+        (status = 401, description = "Invalid MFA token", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "TOTP is already enabled", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    )
+)]
+#[debug_handler]
+pub async fn login_enroll(
+    State(app_state): State<AppState>,
+    enroll_data: ValidatedJson<MfaTokenRequest>,
+) -> Result<Json<TotpEnrollmentResponse>, ControllerError> {
+    let claims = mfa_claims(&app_state, &enroll_data.mfa_token)?;
+
+    let enrollment = start_enrollment(&app_state, claims.user_id, &claims.email).await?;
+
+    Ok(Json(enrollment))
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/totp",
+    tag = "me",
+    responses(
+        (status = 200, description = "TOTP secret to confirm", body = TotpEnrollmentResponse),
+        (status = 409, description = "TOTP is already enabled", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn enroll(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+) -> Result<Json<TotpEnrollmentResponse>, ControllerError> {
+    let enrollment =
+        start_enrollment(&app_state, current_user.user_id, &current_user.email).await?;
+
+    Ok(Json(enrollment))
+}
+
+#[utoipa::path(
+    post,
+    path = "/me/totp/confirm",
+    tag = "me",
+    request_body = TotpCode,
+    responses(
+        (status = 200, description = "TOTP enabled, with the recovery codes", body = RecoveryCodesResponse),
+        (status = 400, description = "Enrollment not started", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Invalid code", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Already exists", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn confirm(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+    code_data: ValidatedJson<TotpCode>,
+) -> Result<Json<RecoveryCodesResponse>, ControllerError> {
+    let totp = find_totp(&app_state, current_user.user_id)
+        .await?
+        .ok_or_else(|| {
+            ControllerError::localized(StatusCode::BAD_REQUEST, "totp_enrollment_not_started")
+        })?;
+
+    if totp.confirmed_at.is_some() {
+        return Err(ControllerError::localized(
+            StatusCode::CONFLICT,
+            "totp_already_enabled",
+        ));
+    }
+
+    check_code(&app_state, &totp, &current_user.email, &code_data.code).await?;
+
+    let recovery_codes = confirm_enrollment(&app_state, current_user.user_id).await?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
+
+// Admins can't turn TOTP off, it is mandatory for them
+#[utoipa::path(
+    delete,
+    path = "/me/totp",
+    tag = "me",
+    request_body = TotpCode,
+    responses(
+        (status = 204, description = "TOTP disabled"),
+        (status = 400, description = "TOTP is not enabled", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Invalid code", body = Problem, content_type = "application/problem+json"),
+        (status = 403, description = "TOTP is mandatory for admins", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn disable(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+    code_data: ValidatedJson<TotpCode>,
+) -> Result<StatusCode, ControllerError> {
+    let user = user::find(&app_state.db_pool, current_user.user_id as u32).await?;
+
+    if user.is_admin {
+        return Err(ControllerError::localized(
+            StatusCode::FORBIDDEN,
+            "totp_required",
+        ));
+    }
+
+    let totp = find_confirmed_totp(&app_state, current_user.user_id).await?;
+    check_code(&app_state, &totp, &current_user.email, &code_data.code).await?;
+
+    users_totp::delete(&app_state.db_pool, current_user.user_id).await?;
+
+    audit(
+        &app_state,
+        current_user.user_id,
+        "TOTP disabled",
+        Severity::Warning,
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// Replaces every recovery code, used or not
+#[utoipa::path(
+    post,
+    path = "/me/totp/recovery-codes",
+    tag = "me",
+    request_body = TotpCode,
+    responses(
+        (status = 200, description = "New recovery codes", body = RecoveryCodesResponse),
+        (status = 400, description = "TOTP is not enabled", body = Problem, content_type = "application/problem+json"),
+        (status = 401, description = "Invalid code", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []))
+)]
+#[debug_handler]
+pub async fn regenerate_recovery_codes(
+    State(app_state): State<AppState>,
+    current_user: CurrentUser,
+    code_data: ValidatedJson<TotpCode>,
+) -> Result<Json<RecoveryCodesResponse>, ControllerError> {
+    let totp = find_confirmed_totp(&app_state, current_user.user_id).await?;
+    check_code(&app_state, &totp, &current_user.email, &code_data.code).await?;
+
+    let (recovery_codes, hashes) = new_recovery_codes(&app_state);
+
+    users_totp::regenerate_recovery_codes(&app_state.db_pool, current_user.user_id, hashes).await?;
+
+    audit(
+        &app_state,
+        current_user.user_id,
+        "TOTP recovery codes regenerated",
+        Severity::Info,
+    )
+    .await?;
+
+    Ok(Json(RecoveryCodesResponse { recovery_codes }))
+}
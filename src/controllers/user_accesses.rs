@@ -1,23 +1,47 @@
-use axum::extract::{Json, Path, State};
-use axum::http::StatusCode;
+use axum::extract::{Json, Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum_macros::debug_handler;
-use serde::Serialize;
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::models::users_accesses::{self, UserAccess};
+use crate::auth::{permissions, Principal};
+use crate::models::days_of_week::DayOfWeek;
+use crate::models::users_accesses::{self, ScheduleEntry, ScheduleFilter, UserAccess};
 use crate::utils::errors::ControllerError;
+use crate::utils::pagination::{Page, PageQuery};
+use crate::utils::validation::ValidatedJson;
+use crate::utils::MappedErrors;
 use crate::AppState;
 
 // Users list response type
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct AccessesListResponse {
-    accesses: Vec<users_accesses::UserAccess>,
+    accesses: Vec<UserAccess>,
 }
 
+#[utoipa::path(
+    post,
+    path = "/user/{user_id}/user-access",
+    tag = "schedules",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    request_body = UserAccessCreate,
+    responses(
+        (status = 201, description = "Access window created"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Already exists", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn create_access(
     State(app_state): State<AppState>,
     Path(user_id): Path<u32>,
-    access_data: Json<users_accesses::UserAccessCreate>,
+    access_data: ValidatedJson<users_accesses::UserAccessCreate>,
 ) -> Result<StatusCode, ControllerError> {
     let access = UserAccess {
         user_id: user_id as i32,
@@ -26,74 +50,311 @@ pub async fn create_access(
         end: access_data.end,
     };
 
-    users_accesses::create(&app_state.db_pool, access)
-        .await
-        .map_err(|err| ControllerError {
-            // TODO: Parse error response for duplicity
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    // A second window on the same day is a conflict, a missing user a foreign key error
+    users_accesses::create(&app_state.db_pool, access).await?;
 
     Ok(StatusCode::CREATED)
 }
 
+#[utoipa::path(
+    get,
+    path = "/user/{user_id}/user-access",
+    operation_id = "list_user_accesses",
+    tag = "schedules",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "Access windows of the user", body = AccessesListResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn find_by_user(
     State(app_state): State<AppState>,
     Path(user_id): Path<u32>,
 ) -> Result<Json<AccessesListResponse>, ControllerError> {
-    let accesses = users_accesses::find(&app_state.db_pool, user_id)
-        .await
-        .map_err(|err| ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    let accesses = users_accesses::find(&app_state.db_pool, user_id).await?;
 
     Ok(Json(AccessesListResponse { accesses }))
 }
 
-// TODO: Implement pagination
+#[utoipa::path(
+    get,
+    path = "/user-access",
+    operation_id = "list_accesses",
+    tag = "schedules",
+    params(
+        PageQuery,
+        users_accesses::UserAccessFilter,
+    ),
+    responses(
+        (status = 200, description = "Page of access windows", body = AccessesPage),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn list_all(
     State(app_state): State<AppState>,
-) -> Result<Json<AccessesListResponse>, ControllerError> {
-    let accesses = users_accesses::list_all(&app_state.db_pool)
-        .await
-        .map_err(|err| ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    Query(page_query): Query<PageQuery>,
+    Query(filter): Query<users_accesses::UserAccessFilter>,
+) -> Result<Json<Page<UserAccess>>, ControllerError> {
+    let page = page_query.resolve(users_accesses::SORT_FIELDS)?;
 
-    Ok(Json(AccessesListResponse { accesses }))
+    let (accesses, total) =
+        users_accesses::list_all(&app_state.db_pool, filter, page.clone()).await?;
+
+    Ok(Json(Page::new(accesses, total, &page)))
 }
 
+#[utoipa::path(
+    put,
+    path = "/user/{user_id}/user-access/{day_id}",
+    tag = "schedules",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+        ("day_id" = u32, Path, description = "Day of the week, 1 to 7"),
+    ),
+    request_body = UserAccessUpdate,
+    responses(
+        (status = 204, description = "Access window updated"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn update_access(
     State(app_state): State<AppState>,
     Path((user_id, day_id)): Path<(u32, u32)>,
-    access_data: Json<users_accesses::UserAccessUpdate>,
+    access_data: ValidatedJson<users_accesses::UserAccessUpdate>,
 ) -> Result<StatusCode, ControllerError> {
-    users_accesses::update(&app_state.db_pool, user_id, day_id, access_data.0)
-        .await
-        .map_err(|err| ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    users_accesses::update(&app_state.db_pool, user_id, day_id, access_data.0).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    delete,
+    path = "/user/{user_id}/user-access/{day_id}",
+    tag = "schedules",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+        ("day_id" = u32, Path, description = "Day of the week, 1 to 7"),
+    ),
+    responses(
+        (status = 200, description = "Access window deleted"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn delete_access(
     State(app_state): State<AppState>,
     Path((user_id, day_id)): Path<(u32, u32)>,
 ) -> Result<StatusCode, ControllerError> {
-    users_accesses::delete(&app_state.db_pool, user_id, day_id)
-        .await
-        .map_err(|err| ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    users_accesses::delete(&app_state.db_pool, user_id, day_id).await?;
 
     Ok(StatusCode::OK)
 }
+
+#[derive(Deserialize, Default, Clone, Copy, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+// `day` is the day number (1 is Sunday) or its name, `at` a time like 08:30
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ScheduleReportQuery {
+    day: Option<String>,
+    at: Option<String>,
+    #[serde(default)]
+    format: ReportFormat,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ScheduleReportResponse {
+    entries: Vec<ScheduleEntry>,
+}
+
+async fn parse_day(app_state: &AppState, day: &str) -> Result<i32, ControllerError> {
+    if let Ok(day_of_week) = day.parse::<i32>() {
+        return match day_of_week {
+            1..=7 => Ok(day_of_week),
+            _ => Err(ControllerError::invalid_query_param("day")),
+        };
+    }
+
+    DayOfWeek::get_day_id_by_name(&app_state.db_pool, day.to_string())
+        .await
+        .map_err(|err| match err {
+            MappedErrors::NotFound => ControllerError::invalid_query_param("day"),
+            _ => ControllerError::from(err),
+        })
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, ControllerError> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+        .map_err(|_| ControllerError::invalid_query_param("at"))
+}
+
+// Values that spreadsheets would take as formulas are prefixed with a quote
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+// The email column is only there when the entries carry the emails
+fn render_csv(entries: &[ScheduleEntry], with_email: bool) -> String {
+    let mut csv = String::from("user_id,username,");
+    if with_email {
+        csv.push_str("email,");
+    }
+    csv.push_str("day_of_week,day,start,end\r\n");
+
+    for entry in entries {
+        csv.push_str(&format!(
+            "{},{},",
+            entry.user_id,
+            csv_field(&entry.username)
+        ));
+        if with_email {
+            csv.push_str(&format!(
+                "{},",
+                csv_field(entry.email.as_deref().unwrap_or_default())
+            ));
+        }
+        csv.push_str(&format!(
+            "{},{},{},{}\r\n",
+            entry.day_of_week,
+            csv_field(&entry.day_name),
+            entry.start.format("%H:%M"),
+            entry.end.format("%H:%M"),
+        ));
+    }
+
+    csv
+}
+
+// Weekly windows of every active user, answers "who can enter on Sunday at 10:00?".
+// The emails are only listed to callers that can also read users
+#[utoipa::path(
+    get,
+    path = "/reports/schedules",
+    tag = "schedules",
+    params(
+        ScheduleReportQuery,
+    ),
+    responses(
+        (status = 200, description = "Schedule of every active user", content(
+            ("application/json" = ScheduleReportResponse),
+            ("text/csv" = String),
+        )),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn schedule_report(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Query(query): Query<ScheduleReportQuery>,
+) -> Result<Response, ControllerError> {
+    let day_of_week = match query.day.as_deref() {
+        Some(day) => Some(parse_day(&app_state, day).await?),
+        None => None,
+    };
+    let at = query.at.as_deref().map(parse_time).transpose()?;
+
+    let mut entries =
+        users_accesses::schedule_report(&app_state.db_pool, ScheduleFilter { day_of_week, at })
+            .await?;
+
+    let with_email = principal
+        .permissions(&app_state.db_pool)
+        .await?
+        .iter()
+        .any(|permission| permission == permissions::USERS_READ);
+    if !with_email {
+        entries.iter_mut().for_each(|entry| entry.email = None);
+    }
+
+    match query.format {
+        ReportFormat::Json => Ok(Json(ScheduleReportResponse { entries }).into_response()),
+        ReportFormat::Csv => Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"schedules.csv\"",
+                ),
+            ],
+            render_csv(&entries, with_email),
+        )
+            .into_response()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> ScheduleEntry {
+        ScheduleEntry {
+            user_id: 7,
+            username: "=HYPERLINK(\"x\")".to_string(),
+            email: Some("ana@example.com".to_string()),
+            day_of_week: 2,
+            day_name: "Monday".to_string(),
+            start: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            end: NaiveTime::from_hms_opt(17, 30, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn neutralizes_formulas() {
+        for value in ["=1+1", "+1", "-1", "@SUM(A1)", "\tcmd", "\rcmd"] {
+            assert!(csv_field(value).trim_start_matches('"').starts_with('\''));
+        }
+        assert_eq!(csv_field("ana"), "ana");
+    }
+
+    #[test]
+    fn quotes_separators_and_quotes() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("a\nb"), "\"a\nb\"");
+    }
+
+    #[test]
+    fn renders_the_email_column_only_when_allowed() {
+        let entries = [entry()];
+
+        assert_eq!(
+            render_csv(&entries, true),
+            "user_id,username,email,day_of_week,day,start,end\r\n\
+             7,\"'=HYPERLINK(\"\"x\"\")\",ana@example.com,2,Monday,08:00,17:30\r\n"
+        );
+        assert_eq!(
+            render_csv(&entries, false),
+            "user_id,username,day_of_week,day,start,end\r\n\
+             7,\"'=HYPERLINK(\"\"x\"\")\",2,Monday,08:00,17:30\r\n"
+        );
+    }
+}
@@ -1,96 +1,199 @@
-use axum::extract::{Json, Path, State};
+use axum::extract::{Json, Path, Query, State};
 use axum::http::StatusCode;
 use axum_macros::debug_handler;
-use serde::Serialize;
 
+use crate::auth::Principal;
+use crate::controllers::password_policy;
+use crate::models::login_lockouts;
 use crate::models::user;
-use crate::utils::errors::ControllerError;
-use crate::utils::MappedErrors;
+use crate::models::user_log::{self, Severity};
+use crate::utils::errors::{ControllerError, PasswordError};
+use crate::utils::pagination::{Page, PageQuery};
+use crate::utils::validation::ValidatedJson;
 use crate::AppState;
 
-#[derive(Debug, Serialize)]
-pub struct UsersListResponse {
-    users: Vec<user::ListUser>,
+async fn audit(
+    app_state: &AppState,
+    user_id: Option<u32>,
+    principal: &Principal,
+    action: String,
+) -> Result<(), ControllerError> {
+    user_log::create(
+        &app_state.db_pool,
+        user_id.map(|id| id as i32),
+        principal.user_id(),
+        action,
+        Severity::Info,
+    )
+    .await
+    .map_err(ControllerError::from)
 }
 
+#[utoipa::path(
+    post,
+    path = "/user",
+    tag = "users",
+    request_body = CreateUser,
+    responses(
+        (status = 201, description = "User created"),
+        (status = 409, description = "Already exists", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn create_user(
     State(app_state): State<AppState>,
-    user_data: Json<user::CreateUser>,
-) -> Result<StatusCode, ControllerError> {
-    user::create(&app_state.db_pool, user_data.0)
-        .await
-        .map_err(|err| ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    principal: Principal,
+    user_data: ValidatedJson<user::CreateUser>,
+) -> Result<StatusCode, PasswordError> {
+    password_policy::enforce(&app_state, None, &user_data.password).await?;
+
+    let password = user_data.password.clone();
+    let action = format!("User '{}' created", user_data.email);
+
+    let user_id = user::create(&app_state.db_pool, user_data.0).await?;
+
+    password_policy::remember(&app_state, user_id, &password).await?;
+    audit(&app_state, Some(user_id as u32), &principal, action).await?;
 
     Ok(StatusCode::CREATED)
 }
 
+#[utoipa::path(
+    get,
+    path = "/user/{id}",
+    tag = "users",
+    params(
+        ("id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "The user", body = ListUser),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn find_user(
     State(app_state): State<AppState>,
     Path(user_id): Path<u32>,
 ) -> Result<Json<user::ListUser>, ControllerError> {
-    let user = user::find(&app_state.db_pool, user_id)
-        .await
-        .map_err(|err| {
-            let status_code = match err {
-                MappedErrors::NotFound => StatusCode::NOT_FOUND,
-                _ => StatusCode::INTERNAL_SERVER_ERROR,
-            };
-            ControllerError {
-                message: err.to_string(),
-                status_code: status_code,
-            }
-        })?;
+    let user = user::find(&app_state.db_pool, user_id).await?;
 
     Ok(Json(user))
 }
 
-// TODO: Implement pagination
+#[utoipa::path(
+    get,
+    path = "/user",
+    operation_id = "list_users",
+    tag = "users",
+    params(
+        PageQuery,
+        user::UserFilter,
+    ),
+    responses(
+        (status = 200, description = "Page of users", body = UsersPage),
+        (status = 400, description = "Invalid request", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn list_all(
     State(app_state): State<AppState>,
-) -> Result<Json<UsersListResponse>, ControllerError> {
-    let users = user::list(&app_state.db_pool)
-        .await
-        .map_err(|err| ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
-
-    Ok(Json(UsersListResponse { users }))
+    Query(page_query): Query<PageQuery>,
+    Query(filter): Query<user::UserFilter>,
+) -> Result<Json<Page<user::ListUser>>, ControllerError> {
+    let page = page_query.resolve(user::SORT_FIELDS)?;
+
+    let (users, total) = user::list(&app_state.db_pool, filter, page.clone()).await?;
+
+    Ok(Json(Page::new(users, total, &page)))
 }
 
+#[utoipa::path(
+    put,
+    path = "/user/{id}",
+    tag = "users",
+    params(
+        ("id" = u32, Path, description = "Id of the user"),
+    ),
+    request_body = UpdateUser,
+    responses(
+        (status = 204, description = "User updated"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 409, description = "Already exists", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn update_user(
     State(app_state): State<AppState>,
+    principal: Principal,
     Path(user_id): Path<u32>,
-    user_data: Json<user::UpdateUser>,
+    user_data: ValidatedJson<user::UpdateUser>,
 ) -> Result<StatusCode, ControllerError> {
-    user::update(&app_state.db_pool, user_id, user_data.0)
-        .await
-        .map_err(|err| ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    user::update(&app_state.db_pool, user_id, user_data.0).await?;
+
+    let action = "User updated".to_string();
+    audit(&app_state, Some(user_id), &principal, action).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    delete,
+    path = "/user/{id}",
+    tag = "users",
+    params(
+        ("id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "User deleted"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
 #[debug_handler]
 pub async fn delete_user(
     State(app_state): State<AppState>,
+    principal: Principal,
     Path(user_id): Path<u32>,
 ) -> Result<StatusCode, ControllerError> {
-    user::disable(&app_state.db_pool, user_id)
-        .await
-        .map_err(|err| ControllerError {
-            message: err.to_string(),
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-        })?;
+    user::disable(&app_state.db_pool, user_id).await?;
+
+    let action = "User disabled".to_string();
+    audit(&app_state, Some(user_id), &principal, action).await?;
 
     Ok(StatusCode::OK)
 }
+
+// Lifts the lockouts caused by failed logins before they expire, of the account and of every IP
+#[utoipa::path(
+    post,
+    path = "/user/{id}/unlock",
+    tag = "users",
+    params(
+        ("id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 204, description = "User unlocked"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn unlock_user(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path(user_id): Path<u32>,
+) -> Result<StatusCode, ControllerError> {
+    user::unlock(&app_state.db_pool, user_id as i32).await?;
+    login_lockouts::clear_all(&app_state.db_pool, user_id as i32).await?;
+
+    let action = "User unlocked".to_string();
+    audit(&app_state, Some(user_id), &principal, action).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,80 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::{users_duress_codes, users_pins};
+use crate::utils::errors::ControllerError;
+use crate::utils::MappedErrors;
+use crate::{auth, AppState};
+
+// The code is only returned once, when it is set. Debug is not derived on purpose, the duress
+// code must never end up in the logs
+#[derive(Serialize, ToSchema)]
+pub struct DuressCodeResponse {
+    code: String,
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/{user_id}/duress",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "Duress code set, with the code for the user", body = DuressCodeResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 503, description = "No free code found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn set_duress_code(
+    State(app_state): State<AppState>,
+    Path(user_id): Path<u32>,
+) -> Result<Json<DuressCodeResponse>, ControllerError> {
+    // Duress codes share the keypad with PINs, so they can't collide with any of them. Like the
+    // PINs they are random, refusing a code that is taken would tell the client someone's code.
+    // The UNIQUE index refuses the duress codes taken, the PINs are checked apart
+    for _ in 0..auth::CODE_ATTEMPTS {
+        let code = auth::generate_numeric_code(users_pins::MIN_PIN_DIGITS);
+        let code_hash = auth::hash_pin(&app_state.config.pin_pepper, &code);
+
+        if users_pins::exists(&app_state.db_pool, code_hash.clone()).await? {
+            continue;
+        }
+
+        match users_duress_codes::set(&app_state.db_pool, user_id, code_hash).await {
+            Ok(_) => return Ok(Json(DuressCodeResponse { code })),
+            Err(MappedErrors::Conflict) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Err(auth::codes_exhausted())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/{user_id}/duress",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "Duress code deleted"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn delete_duress_code(
+    State(app_state): State<AppState>,
+    Path(user_id): Path<u32>,
+) -> Result<StatusCode, ControllerError> {
+    users_duress_codes::delete(&app_state.db_pool, user_id).await?;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,116 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::models::users_duress_codes;
+use crate::models::users_pins::{self, ListUserPin};
+use crate::utils::errors::ControllerError;
+use crate::utils::validation::ValidatedJson;
+use crate::utils::MappedErrors;
+use crate::{auth, AppState};
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PinsListResponse {
+    pins: Vec<ListUserPin>,
+}
+
+// The PIN is only returned once, on creation
+#[derive(Serialize, ToSchema)]
+pub struct PinCreatedResponse {
+    pin: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/{user_id}/pin",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    request_body = UserPinCreate,
+    responses(
+        (status = 201, description = "PIN created, with the PIN for the user", body = PinCreatedResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+        (status = 503, description = "No free PIN found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn create_pin(
+    State(app_state): State<AppState>,
+    Path(user_id): Path<u32>,
+    pin_data: ValidatedJson<users_pins::UserPinCreate>,
+) -> Result<(StatusCode, Json<PinCreatedResponse>), ControllerError> {
+    let digits = pin_data.digits.unwrap_or(users_pins::MIN_PIN_DIGITS);
+
+    // A PIN chosen by the client couldn't be refused for being taken without telling them
+    // someone else's PIN, so they are random and the ones taken are silently skipped. The
+    // UNIQUE index refuses the PINs taken, the duress codes are checked apart
+    for _ in 0..auth::CODE_ATTEMPTS {
+        let pin = auth::generate_numeric_code(digits);
+        let pin_hash = auth::hash_pin(&app_state.config.pin_pepper, &pin);
+
+        if users_duress_codes::exists(&app_state.db_pool, pin_hash.clone()).await? {
+            continue;
+        }
+
+        match users_pins::create(&app_state.db_pool, user_id, pin_hash).await {
+            Ok(_) => return Ok((StatusCode::CREATED, Json(PinCreatedResponse { pin }))),
+            Err(MappedErrors::Conflict) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Err(auth::codes_exhausted())
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/{user_id}/pin",
+    operation_id = "list_user_pins",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "PINs of the user", body = PinsListResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn find_by_user(
+    State(app_state): State<AppState>,
+    Path(user_id): Path<u32>,
+) -> Result<Json<PinsListResponse>, ControllerError> {
+    let pins = users_pins::find(&app_state.db_pool, user_id).await?;
+
+    Ok(Json(PinsListResponse { pins }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/{user_id}/pin/{pin_id}",
+    tag = "credentials",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+        ("pin_id" = u32, Path, description = "Id of the PIN"),
+    ),
+    responses(
+        (status = 200, description = "PIN deleted"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn delete_pin(
+    State(app_state): State<AppState>,
+    Path((user_id, pin_id)): Path<(u32, u32)>,
+) -> Result<StatusCode, ControllerError> {
+    users_pins::delete(&app_state.db_pool, user_id, pin_id).await?;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,150 @@
+use axum::extract::{Json, Path, State};
+use axum::http::StatusCode;
+use axum_macros::debug_handler;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::auth::Principal;
+use crate::controllers::door;
+use crate::models::user_log::{self, Severity};
+use crate::models::visits::{self, VisitWithDoors};
+use crate::utils::errors::ControllerError;
+use crate::utils::validation::ValidatedJson;
+use crate::utils::MappedErrors;
+use crate::{auth, AppState};
+
+const VISIT_CODE_DIGITS: usize = 8;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VisitsListResponse {
+    visits: Vec<VisitWithDoors>,
+}
+
+// The access code is only returned once, on creation
+#[derive(Serialize, ToSchema)]
+pub struct VisitCreatedResponse {
+    id: i32,
+    code: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/{user_id}/visit",
+    tag = "visits",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    request_body = VisitCreate,
+    responses(
+        (status = 201, description = "Visit created, with the code for the visitor", body = VisitCreatedResponse),
+        (status = 404, description = "User or door not found", body = Problem, content_type = "application/problem+json"),
+        (status = 422, description = "Invalid body", body = Problem, content_type = "application/problem+json"),
+        (status = 503, description = "No free code found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn create_visit(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path(host_user_id): Path<u32>,
+    visit_data: ValidatedJson<visits::VisitCreate>,
+) -> Result<(StatusCode, Json<VisitCreatedResponse>), ControllerError> {
+    for door_id in &visit_data.doors {
+        door::ensure_known_door(&app_state, *door_id)?;
+    }
+
+    // Codes are random, the UNIQUE index refuses the ones taken by another visit
+    let mut created = None;
+    for _ in 0..auth::CODE_ATTEMPTS {
+        let code = auth::generate_numeric_code(VISIT_CODE_DIGITS);
+        let code_hash = auth::hash_pin(&app_state.config.pin_pepper, &code);
+
+        match visits::create(
+            &app_state.db_pool,
+            host_user_id,
+            visit_data.0.clone(),
+            code_hash,
+        )
+        .await
+        {
+            Ok(id) => {
+                created = Some((id, code));
+                break;
+            }
+            Err(MappedErrors::Conflict) => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    let (id, code) = created.ok_or_else(auth::codes_exhausted)?;
+
+    let guest_name = &visit_data.guest_name;
+
+    user_log::create(
+        &app_state.db_pool,
+        Some(host_user_id as i32),
+        principal.user_id(),
+        format!("Visit #{} created for guest '{}'", id, guest_name),
+        Severity::Info,
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(VisitCreatedResponse { id, code })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/{user_id}/visit",
+    tag = "visits",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+    ),
+    responses(
+        (status = 200, description = "Visits hosted by the user", body = VisitsListResponse),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn find_by_host(
+    State(app_state): State<AppState>,
+    Path(host_user_id): Path<u32>,
+) -> Result<Json<VisitsListResponse>, ControllerError> {
+    let visits = visits::find_by_host(&app_state.db_pool, host_user_id).await?;
+
+    Ok(Json(VisitsListResponse { visits }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/{user_id}/visit/{visit_id}",
+    tag = "visits",
+    params(
+        ("user_id" = u32, Path, description = "Id of the user"),
+        ("visit_id" = u32, Path, description = "Id of the visit"),
+    ),
+    responses(
+        (status = 200, description = "Visit cancelled"),
+        (status = 404, description = "Not found", body = Problem, content_type = "application/problem+json"),
+    ),
+    security(("bearer" = []), ("api_key" = []))
+)]
+#[debug_handler]
+pub async fn cancel_visit(
+    State(app_state): State<AppState>,
+    principal: Principal,
+    Path((host_user_id, visit_id)): Path<(u32, u32)>,
+) -> Result<StatusCode, ControllerError> {
+    visits::cancel(&app_state.db_pool, host_user_id, visit_id).await?;
+
+    user_log::create(
+        &app_state.db_pool,
+        Some(host_user_id as i32),
+        principal.user_id(),
+        format!("Visit #{} cancelled", visit_id),
+        Severity::Info,
+    )
+    .await?;
+
+    Ok(StatusCode::OK)
+}
@@ -1,19 +1,43 @@
 use axum_macros::FromRef;
 use deadpool_diesel::mysql::Pool;
-use std::{env, net::SocketAddr, sync::Arc};
+use log::{error, info};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use auth::denylist::TokenDenylist;
+use auth::door_token::DoorTokenKeys;
+use auth::keyring::{Keyring, KeyringHandle};
+use auth::oidc::OidcClient;
+use config::Config;
+use services::mailer::Mailer;
+use services::throttle::FailureLimiter;
 
 pub mod auth;
+pub mod config;
 pub mod controllers;
 pub mod middlewares;
 pub mod models;
+pub mod openapi;
 pub mod routes;
 pub mod services;
 pub mod utils;
 
 #[derive(Clone, FromRef)]
 pub struct AppState {
+    config: Arc<Config>,
     db_pool: Pool,
     mqtt_cli: Arc<rumqttc::AsyncClient>,
+    pin_attempts: Arc<FailureLimiter>,
+    #[from_ref(skip)]
+    keypad_attempts: Arc<FailureLimiter>,
+    #[from_ref(skip)]
+    totp_attempts: Arc<FailureLimiter>,
+    #[from_ref(skip)]
+    login_attempts: Arc<FailureLimiter>,
+    door_token_keys: Option<Arc<DoorTokenKeys>>,
+    token_denylist: Arc<TokenDenylist>,
+    keyring: Arc<KeyringHandle>,
+    mailer: Arc<dyn Mailer>,
+    oidc: Option<Arc<OidcClient>>,
 }
 
 #[tokio::main]
@@ -24,33 +48,81 @@ async fn main() {
         .filter_level(log::LevelFilter::Error)
         .init();
 
+    // Every setting is checked before anything starts, the problems are reported together
+    let config = Arc::new(Config::load().unwrap_or_else(|err| panic!("{}", err)));
+    info!("{:#?}", config);
+
     // Initialize MQTT client
-    services::mqtt::init_main_client();
-    let route_cli = Arc::new(services::mqtt::init_route_client().to_owned());
+    services::mqtt::init_main_client(&config.mqtt);
+    let route_cli = Arc::new(services::mqtt::init_route_client(&config.mqtt).to_owned());
+
+    let keyring = Keyring::load(&config).unwrap_or_else(|err| panic!("{}", err));
+    let keyring = Arc::new(KeyringHandle::new(keyring));
+    tokio::spawn(reload_keyring_on_hangup(config.clone(), keyring.clone()));
+
+    let db_pool = services::sql::establish_connection(&config.database_url);
+    let token_denylist = Arc::new(TokenDenylist::load(&db_pool).await);
+    tokio::spawn(refresh_denylist(db_pool.clone(), token_denylist.clone()));
 
     // Initialize AppState, shared state between routes
     let state = AppState {
-        db_pool: services::sql::establish_connection(),
+        db_pool,
         mqtt_cli: route_cli,
+        // Block a door keypad for 5 minutes after 5 wrong PINs
+        pin_attempts: Arc::new(FailureLimiter::new(5, Duration::from_secs(5 * 60))),
+        // Wrong PINs, visit codes and badges from a single IP, across every door
+        keypad_attempts: Arc::new(FailureLimiter::new(20, Duration::from_secs(15 * 60))),
+        // Same for the TOTP and recovery codes of a user
+        totp_attempts: Arc::new(FailureLimiter::new(5, Duration::from_secs(5 * 60))),
+        // Wrong passwords from a single IP, across every account
+        login_attempts: Arc::new(FailureLimiter::new(20, Duration::from_secs(15 * 60))),
+        door_token_keys: DoorTokenKeys::from_config(&config).map(Arc::new),
+        token_denylist,
+        keyring,
+        mailer: services::mailer::init_mailer(&config.mail),
+        oidc: OidcClient::from_config(&config).map(Arc::new),
+        config: config.clone(),
     };
 
     let app = routes::builder(state);
 
-    let host = env::var("GCA_ACCESS_SERVER_HOST").expect("GCA_ACCESS_SERVER_HOST not set");
-    let port = env::var("GCA_ACCESS_SERVER_PORT").expect("GCA_ACCESS_SERVER_PORT not set");
-
-    let addr = format!("{}:{}", host, port)
-        .parse::<SocketAddr>()
-        .expect("Invalid address or port");
+    let addr = config.listen_addr;
 
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .expect("Failed to bind to address");
 
-    axum::serve(listener, app.into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+    // Connection info gives the login throttling the client IP
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
+}
+
+// Keys can be rotated without downtime: update the keyring file and send SIGHUP
+async fn reload_keyring_on_hangup(config: Arc<Config>, keyring: Arc<KeyringHandle>) {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("Expect hangup signal handler");
+
+    while hangup.recv().await.is_some() {
+        keyring.reload(&config);
+    }
+}
+
+// Other instances of the API share the `revoked_tokens` table, not the denylist in memory
+async fn refresh_denylist(db_pool: Pool, token_denylist: Arc<TokenDenylist>) {
+    let mut interval = tokio::time::interval(auth::denylist::REFRESH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(err) = token_denylist.refresh(&db_pool).await {
+            error!("Unable to refresh the revoked tokens: {}", err);
+        }
+    }
 }
 
 async fn shutdown_signal() {
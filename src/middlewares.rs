@@ -1 +1,4 @@
-pub mod auth;
\ No newline at end of file
+pub mod auth;
+pub mod deprecation;
+pub mod permissions;
+pub mod request_context;
@@ -1,43 +1,123 @@
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{Request, State},
+    http::{header, HeaderMap, Method},
     middleware::Next,
     response::Response,
 };
-use log::warn;
-use serde::{Deserialize, Serialize};
+use log::{error, info, warn};
 
-use crate::auth::validate_token;
+use crate::auth::principal::ApiKeyPrincipal;
+use crate::auth::{hash_token, validate_token, CurrentUser, Principal};
+use crate::models::api_keys;
+use crate::models::user_log::{self, Severity};
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+use crate::AppState;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Claims {
-    aud: String,
-    sub: String,
-    company: String,
-    exp: u64,
+fn unauthorized() -> ControllerError {
+    ControllerError::from_type(ControllerErrorType::Unauthorized)
 }
 
-pub async fn intercept_request(request: Request, next: Next) -> Result<Response, StatusCode> {
-    let token = request
-        .headers()
+enum Credential {
+    Bearer(String),
+    ApiKey(String),
+}
+
+// `Authorization: Bearer <jwt>`, `Authorization: ApiKey <key>` or `X-API-Key: <key>`
+fn credential(headers: &HeaderMap) -> Option<Credential> {
+    if let Some(authorization) = headers
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok())
-        .and_then(|header| {
-            header
-                .strip_prefix("Bearer ")
-                .map(|stripped| stripped.to_owned())
-        });
-
-    let token = match token {
-        Some(token) => token,
+    {
+        if let Some(token) = authorization.strip_prefix("Bearer ") {
+            return Some(Credential::Bearer(token.to_owned()));
+        }
+        if let Some(key) = authorization.strip_prefix("ApiKey ") {
+            return Some(Credential::ApiKey(key.to_owned()));
+        }
+    }
+
+    headers
+        .get("x-api-key")
+        .and_then(|header| header.to_str().ok())
+        .map(|key| Credential::ApiKey(key.to_owned()))
+}
+
+fn authenticate_token(state: &AppState, token: String) -> Result<Principal, ControllerError> {
+    let claims = match validate_token(&state.keyring.get(), token) {
+        Some(claims) => claims,
+        None => return Err(unauthorized()),
+    };
+
+    if state.token_denylist.is_revoked(&claims.jti) {
+        warn!("Revoked token used by user {}", claims.user_id);
+        return Err(unauthorized());
+    }
+
+    Ok(Principal::User(CurrentUser::from(claims)))
+}
+
+// Every use updates `last_used_at`, the ones that change something also go to the audit log
+async fn authenticate_api_key(
+    state: &AppState,
+    key: String,
+    method: &Method,
+    path: &str,
+) -> Result<Principal, ControllerError> {
+    let api_key = match api_keys::find_by_hash(&state.db_pool, hash_token(&key)).await {
+        Ok(api_key) if api_key.is_usable() => api_key,
+        Ok(api_key) => {
+            warn!("Revoked or expired API key #{} used", api_key.id);
+            return Err(unauthorized());
+        }
+        Err(_) => return Err(unauthorized()),
+    };
+
+    info!(
+        "API key #{} '{}' used for {} {}",
+        api_key.id, api_key.name, method, path
+    );
+
+    if let Err(err) = api_keys::touch(&state.db_pool, api_key.id).await {
+        error!("Error updating API key #{}: {}", api_key.id, err);
+    }
+
+    if method != Method::GET {
+        let action = format!(
+            "API key #{} '{}' used for {} {}",
+            api_key.id, api_key.name, method, path
+        );
+        if let Err(err) = user_log::create(&state.db_pool, None, None, action, Severity::Info).await
+        {
+            error!("Error writing audit entry: {}", err);
+        }
+    }
+
+    Ok(Principal::ApiKey(ApiKeyPrincipal {
+        id: api_key.id,
+        scopes: api_key.scope_list(),
+        name: api_key.name,
+    }))
+}
+
+pub async fn intercept_request(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ControllerError> {
+    let principal = match credential(request.headers()) {
+        Some(Credential::Bearer(token)) => authenticate_token(&state, token)?,
+        Some(Credential::ApiKey(key)) => {
+            let path = request.uri().path().to_owned();
+            authenticate_api_key(&state, key, request.method(), &path).await?
+        }
         None => {
             warn!("No token provided");
-            return Err(StatusCode::UNAUTHORIZED);
+            return Err(unauthorized());
         }
     };
 
-    match validate_token(token) {
-        true => Ok(next.run(request).await),
-        false => Err(StatusCode::UNAUTHORIZED),
-    }
+    // Handlers get the caller by taking a `Principal` or `CurrentUser` argument
+    request.extensions_mut().insert(principal);
+
+    Ok(next.run(request).await)
 }
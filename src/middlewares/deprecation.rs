@@ -0,0 +1,42 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::routes::V1_PREFIX;
+
+// Date the unversioned paths were deprecated (RFC 9745, `@` + unix time) and the date they
+// stop being served (RFC 8594)
+const DEPRECATED_AT: &str = "@1792368000";
+const SUNSET_AT: &str = "Mon, 19 Apr 2027 00:00:00 GMT";
+
+static DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+static SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+// Layer of the unversioned aliases (`/user`, `/login`...), answers like `/api/v1` and tells
+// the client where the route moved to. Well-known URIs (RFC 8615) belong at the root and
+// stay there for good
+pub async fn legacy_alias(request: Request, next: Next) -> Response {
+    if request.uri().path().starts_with("/.well-known/") {
+        return next.run(request).await;
+    }
+
+    let successor = match request.uri().path() {
+        "/" => V1_PREFIX.to_string(),
+        path => format!("{}{}", V1_PREFIX, path),
+    };
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+
+    headers.insert(DEPRECATION.clone(), HeaderValue::from_static(DEPRECATED_AT));
+    headers.insert(SUNSET.clone(), HeaderValue::from_static(SUNSET_AT));
+    if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor))
+    {
+        headers.insert(header::LINK, link);
+    }
+
+    response
+}
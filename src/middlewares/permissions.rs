@@ -0,0 +1,51 @@
+use axum::{extract::Request, http::StatusCode, middleware::Next, response::Response};
+use log::{error, warn};
+
+use crate::auth::Principal;
+use crate::utils::errors::{ControllerError, ControllerErrorType};
+use crate::utils::MappedErrors;
+use crate::AppState;
+
+// Runs after `auth::intercept_request`. Users need the permission through one of their
+// roles, API keys through their scopes
+pub async fn require(
+    state: AppState,
+    permission: &'static str,
+    request: Request,
+    next: Next,
+) -> Result<Response, ControllerError> {
+    let Some(principal) = request.extensions().get::<Principal>() else {
+        return Err(ControllerError::from_type(
+            ControllerErrorType::Unauthorized,
+        ));
+    };
+
+    // A token can outlive its user or the user being active, who is then no longer authenticated
+    let granted = match principal.permissions(&state.db_pool).await {
+        Ok(granted) => granted,
+        Err(MappedErrors::NotFound) => {
+            return Err(ControllerError::from_type(
+                ControllerErrorType::Unauthorized,
+            ))
+        }
+        Err(err) => {
+            error!(
+                "Error loading permissions of user {:?}: {}",
+                principal.user_id(),
+                err
+            );
+            return Err(ControllerError::from(err));
+        }
+    };
+
+    if !granted.iter().any(|granted| granted == permission) {
+        warn!("Caller without '{}' refused", permission);
+        return Err(ControllerError::localized_with(
+            StatusCode::FORBIDDEN,
+            "missing_permission",
+            &[("permission", permission)],
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,115 @@
+use axum::{
+    body::to_bytes,
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use log::{debug, info};
+
+use crate::auth::generate_random_token;
+use crate::utils::errors::ControllerError;
+use crate::utils::i18n::{self, Locale};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Bodies of the error responses axum builds itself are short plain text
+const MAX_ERROR_BODY: usize = 16 * 1024;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// Id of the request being handled, shown on the error responses and the logs
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+// Valid ids given by a proxy in front of us are kept, so its logs can be matched with ours
+fn request_id(request: &Request) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= 64
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| generate_random_token(16))
+}
+
+fn is_problem(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == "application/problem+json")
+}
+
+// Errors axum answers on its own (unknown routes, rejected extractors...) are turned into
+// problem+json as well, keeping their status. Their plain text is English only and tells
+// about our types, so the client gets the message of the status and the text is only logged
+async fn into_problem(response: Response) -> Response {
+    let (parts, body) = response.into_parts();
+    if let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY).await {
+        if !bytes.is_empty() {
+            debug!("{} {}", parts.status, String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    let mut problem = ControllerError::from_status(parts.status).into_response();
+    for (name, value) in parts.headers.iter() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            problem.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+
+    problem
+}
+
+// Outermost layer, gives every request an id and a language, and makes sure every error
+// is problem+json
+pub async fn handle(request: Request, next: Next) -> Response {
+    let id = request_id(&request);
+    let locale = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(Locale::from_accept_language)
+        .unwrap_or_default();
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+
+    let handled = REQUEST_ID.scope(id.clone(), async move {
+        let mut response = next.run(request).await;
+
+        if (response.status().is_client_error() || response.status().is_server_error())
+            && !is_problem(&response)
+        {
+            response = into_problem(response).await;
+        }
+
+        info!(
+            "[{}] {} {} {}",
+            id,
+            method,
+            path,
+            response.status().as_u16()
+        );
+
+        if let Ok(value) = HeaderValue::from_str(&id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+        response.headers_mut().insert(
+            header::CONTENT_LANGUAGE,
+            HeaderValue::from_static(locale.tag()),
+        );
+
+        response
+    });
+
+    i18n::with_locale(locale, handled).await
+}
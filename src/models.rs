@@ -1,6 +1,19 @@
+pub mod api_keys;
+pub mod credentials;
 pub mod days_of_week;
+pub mod login_lockouts;
+pub mod password_history;
+pub mod password_policy;
+pub mod password_reset_tokens;
+pub mod refresh_tokens;
+pub mod revoked_tokens;
+pub mod roles;
 pub mod user;
 pub mod user_log;
 pub mod users_accesses;
+pub mod users_duress_codes;
+pub mod users_pins;
+pub mod users_totp;
+pub mod visits;
 
-pub mod schema;
\ No newline at end of file
+pub mod schema;
@@ -0,0 +1,206 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::models::schema::api_keys;
+use crate::utils::{ensure_affected, error_mapper, MappedErrors};
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::models::schema::api_keys)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: String,
+    pub created_by: Option<i32>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiKey {
+    pub fn scope_list(&self) -> Vec<String> {
+        self.scopes
+            .split(',')
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    pub fn is_usable(&self) -> bool {
+        self.revoked_at.is_none()
+            && self
+                .expires_at
+                .is_none_or(|expires_at| expires_at > Utc::now().naive_utc())
+    }
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct ApiKeyCreate {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1), custom = "crate::auth::permissions::validate_known")]
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct ApiKeyUpdate {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(min = 1), custom = "crate::auth::permissions::validate_known")]
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    api_key: ApiKeyCreate,
+    key_prefix: String,
+    key_hash: String,
+    created_by: Option<i32>,
+) -> Result<i32, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let api_key_id = conn
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                diesel::insert_into(api_keys::table)
+                    .values((
+                        api_keys::name.eq(api_key.name),
+                        api_keys::key_prefix.eq(key_prefix),
+                        api_keys::key_hash.eq(key_hash.clone()),
+                        api_keys::scopes.eq(api_key.scopes.join(",")),
+                        api_keys::created_by.eq(created_by),
+                        api_keys::expires_at.eq(api_key.expires_at),
+                    ))
+                    .execute(conn)?;
+
+                api_keys::table
+                    .filter(api_keys::key_hash.eq(key_hash))
+                    .select(api_keys::id)
+                    .first::<i32>(conn)
+            })
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(api_key_id)
+}
+
+pub async fn list(pool: &deadpool_diesel::mysql::Pool) -> Result<Vec<ApiKey>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            api_keys::table
+                .select(ApiKey::as_select())
+                .load::<ApiKey>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
+
+pub async fn find(pool: &deadpool_diesel::mysql::Pool, id: u32) -> Result<ApiKey, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            api_keys::table
+                .find(id as i32)
+                .select(ApiKey::as_select())
+                .first::<ApiKey>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+pub async fn find_by_hash(
+    pool: &deadpool_diesel::mysql::Pool,
+    key_hash: String,
+) -> Result<ApiKey, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            api_keys::table
+                .filter(api_keys::key_hash.eq(key_hash))
+                .select(ApiKey::as_select())
+                .first::<ApiKey>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+pub async fn update(
+    pool: &deadpool_diesel::mysql::Pool,
+    id: u32,
+    api_key: ApiKeyUpdate,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let rows = conn
+        .interact(move |conn| {
+            diesel::update(api_keys::table.find(id as i32))
+                .set((
+                    api_keys::name.eq(api_key.name),
+                    api_keys::scopes.eq(api_key.scopes.join(",")),
+                    api_keys::expires_at.eq(api_key.expires_at),
+                ))
+                .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    ensure_affected(rows)
+}
+
+pub async fn touch(pool: &deadpool_diesel::mysql::Pool, id: i32) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::update(api_keys::table.find(id))
+            .set(api_keys::last_used_at.eq(Utc::now().naive_utc()))
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Revoked keys are kept, so their use can still be traced back to them
+pub async fn revoke(pool: &deadpool_diesel::mysql::Pool, id: u32) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::update(
+            api_keys::table
+                .find(id as i32)
+                .filter(api_keys::revoked_at.is_null()),
+        )
+        .set(api_keys::revoked_at.eq(Utc::now().naive_utc()))
+        .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
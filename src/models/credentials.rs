@@ -0,0 +1,215 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::models::schema::{credentials, users};
+use crate::utils::{ensure_affected, error_mapper, MappedErrors};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BadgeStatus {
+    Active,
+    Lost,
+    Revoked,
+}
+
+impl BadgeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BadgeStatus::Active => "active",
+            BadgeStatus::Lost => "lost",
+            BadgeStatus::Revoked => "revoked",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "active" => Some(BadgeStatus::Active),
+            "lost" => Some(BadgeStatus::Lost),
+            "revoked" => Some(BadgeStatus::Revoked),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::models::schema::credentials)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct Credential {
+    pub id: i32,
+    pub user_id: i32,
+    pub badge_uid: String,
+    pub status: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct CredentialCreate {
+    #[validate(custom = "validate_uid")]
+    pub badge_uid: String,
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct CredentialUpdate {
+    pub status: BadgeStatus,
+}
+
+// Readers may send the UID with separators or in lowercase ("04:a2:2b:..."), keep a single format
+pub fn normalize_uid(badge_uid: &str) -> String {
+    badge_uid
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+// The UID is stored normalized, so the length is checked after the separators are dropped
+pub fn validate_uid(badge_uid: &str) -> Result<(), ValidationError> {
+    let length = normalize_uid(badge_uid).len();
+
+    if (4..=64).contains(&length) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_badge_uid"))
+    }
+}
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+    badge_uid: String,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(credentials::table)
+            .values((
+                credentials::user_id.eq(user_id as i32),
+                credentials::badge_uid.eq(badge_uid),
+                credentials::status.eq(BadgeStatus::Active.as_str()),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+pub async fn find(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+) -> Result<Vec<Credential>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            credentials::table
+                .filter(credentials::user_id.eq(user_id as i32))
+                .select(Credential::as_select())
+                .load::<Credential>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
+
+pub async fn find_by_uid(
+    pool: &deadpool_diesel::mysql::Pool,
+    badge_uid: String,
+) -> Result<Credential, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            credentials::table
+                .filter(credentials::badge_uid.eq(badge_uid))
+                .select(Credential::as_select())
+                .first::<Credential>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Find the owner of an active badge, only active users are able to unlock doors
+pub async fn find_user_by_badge(
+    pool: &deadpool_diesel::mysql::Pool,
+    badge_uid: String,
+) -> Result<i32, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            credentials::table
+                .inner_join(users::table)
+                .filter(credentials::badge_uid.eq(badge_uid))
+                .filter(credentials::status.eq(BadgeStatus::Active.as_str()))
+                .filter(users::is_active.eq(true))
+                .select(users::id)
+                .first::<i32>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+pub async fn find_one(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+    credential_id: u32,
+) -> Result<Credential, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            credentials::table
+                .filter(credentials::id.eq(credential_id as i32))
+                .filter(credentials::user_id.eq(user_id as i32))
+                .select(Credential::as_select())
+                .first::<Credential>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Only changes the badge if it is still in the `from` status, a concurrent change is not found
+pub async fn update_status(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+    credential_id: u32,
+    from: BadgeStatus,
+    status: BadgeStatus,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let rows = conn
+        .interact(move |conn| {
+            diesel::update(
+                credentials::table
+                    .filter(credentials::id.eq(credential_id as i32))
+                    .filter(credentials::user_id.eq(user_id as i32))
+                    .filter(credentials::status.eq(from.as_str())),
+            )
+            .set(credentials::status.eq(status.as_str()))
+            .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    ensure_affected(rows)
+}
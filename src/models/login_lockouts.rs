@@ -0,0 +1,116 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::models::schema::login_lockouts;
+use crate::utils::{error_mapper, MappedErrors};
+
+// Until when the account is locked for the IP, if it is
+pub async fn find_locked_until(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    ip: String,
+) -> Result<Option<NaiveDateTime>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            login_lockouts::table
+                .find((user_id, ip))
+                .select(login_lockouts::locked_until)
+                .first::<Option<NaiveDateTime>>(conn)
+                .optional()
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result.flatten())
+}
+
+// Counts a wrong password from the IP, once `max_attempts` is reached the account is locked
+// for that IP until `lock_until` and the counter starts over. Returns the attempts counted and
+// if it got locked
+pub async fn register_failure(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    ip: String,
+    max_attempts: i32,
+    lock_until: NaiveDateTime,
+) -> Result<(i32, bool), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                diesel::insert_into(login_lockouts::table)
+                    .values((
+                        login_lockouts::user_id.eq(user_id),
+                        login_lockouts::ip.eq(&ip),
+                        login_lockouts::failed_attempts.eq(1),
+                    ))
+                    .on_conflict(diesel::dsl::DuplicatedKeys)
+                    .do_update()
+                    .set(login_lockouts::failed_attempts.eq(login_lockouts::failed_attempts + 1))
+                    .execute(conn)?;
+
+                let attempts = login_lockouts::table
+                    .find((user_id, &ip))
+                    .select(login_lockouts::failed_attempts)
+                    .first::<i32>(conn)?;
+
+                if attempts < max_attempts {
+                    return Ok::<_, diesel::result::Error>((attempts, false));
+                }
+
+                diesel::update(login_lockouts::table.find((user_id, &ip)))
+                    .set((
+                        login_lockouts::failed_attempts.eq(0),
+                        login_lockouts::locked_until.eq(lock_until),
+                    ))
+                    .execute(conn)?;
+
+                Ok((attempts, true))
+            })
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Clears the failed attempts and the lock of the IP, after a successful login from it
+pub async fn clear(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    ip: String,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::delete(login_lockouts::table.find((user_id, ip))).execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Clears every lock of the account, by an admin
+pub async fn clear_all(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::delete(login_lockouts::table.filter(login_lockouts::user_id.eq(user_id)))
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
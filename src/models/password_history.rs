@@ -0,0 +1,50 @@
+use diesel::prelude::*;
+
+use crate::models::schema::password_history;
+use crate::utils::{error_mapper, MappedErrors};
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    password_hash: String,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(password_history::table)
+            .values((
+                password_history::user_id.eq(user_id),
+                password_history::password_hash.eq(password_hash),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Hashes of the last `limit` passwords of the user, newest first
+pub async fn find_recent(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    limit: i64,
+) -> Result<Vec<String>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            password_history::table
+                .filter(password_history::user_id.eq(user_id))
+                .order(password_history::id.desc())
+                .limit(limit)
+                .select(password_history::password_hash)
+                .load::<String>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
@@ -0,0 +1,71 @@
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::models::schema::password_policy;
+use crate::utils::{error_mapper, MappedErrors};
+
+// The policy is a single row, created by the migration
+const POLICY_ID: i32 = 1;
+
+#[derive(Queryable, Selectable, Serialize, Debug, ToSchema)]
+#[diesel(table_name = crate::models::schema::password_policy)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct PasswordPolicy {
+    pub min_length: i32,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    // How many of the previous passwords can't be used again, 0 allows any
+    pub history_size: i32,
+}
+
+#[derive(AsChangeset, Deserialize, Debug, Validate, ToSchema)]
+#[diesel(table_name = crate::models::schema::password_policy)]
+pub struct PasswordPolicyUpdate {
+    #[validate(range(min = 8, max = 128))]
+    pub min_length: i32,
+    pub require_uppercase: bool,
+    pub require_lowercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    #[validate(range(min = 0, max = 24))]
+    pub history_size: i32,
+}
+
+pub async fn find(pool: &deadpool_diesel::mysql::Pool) -> Result<PasswordPolicy, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            password_policy::table
+                .find(POLICY_ID)
+                .select(PasswordPolicy::as_select())
+                .first::<PasswordPolicy>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+pub async fn update(
+    pool: &deadpool_diesel::mysql::Pool,
+    policy: PasswordPolicyUpdate,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::update(password_policy::table.find(POLICY_ID))
+            .set(&policy)
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
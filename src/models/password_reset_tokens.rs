@@ -0,0 +1,125 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::models::schema::password_reset_tokens;
+use crate::utils::{error_mapper, MappedErrors};
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::models::schema::password_reset_tokens)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct PasswordResetToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::models::schema::password_reset_tokens)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct PasswordResetTokenCreate {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    reset_token: PasswordResetTokenCreate,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(password_reset_tokens::table)
+            .values(&reset_token)
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Number of tokens requested for the user since the given time, used to rate limit the requests
+pub async fn count_since(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    since: NaiveDateTime,
+) -> Result<i64, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let count = conn
+        .interact(move |conn| {
+            password_reset_tokens::table
+                .filter(password_reset_tokens::user_id.eq(user_id))
+                .filter(password_reset_tokens::created_at.ge(since))
+                .count()
+                .get_result::<i64>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(count)
+}
+
+pub async fn find_by_hash(
+    pool: &deadpool_diesel::mysql::Pool,
+    token_hash: String,
+) -> Result<PasswordResetToken, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            password_reset_tokens::table
+                .filter(password_reset_tokens::token_hash.eq(token_hash))
+                .select(PasswordResetToken::as_select())
+                .first::<PasswordResetToken>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Marks every pending token of the user as used, returns false when the given token was
+// already used, e.g. by a concurrent request
+pub async fn use_token(
+    pool: &deadpool_diesel::mysql::Pool,
+    id: i32,
+    user_id: i32,
+) -> Result<bool, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                let now = Utc::now().naive_utc();
+
+                let affected = diesel::update(
+                    password_reset_tokens::table
+                        .filter(password_reset_tokens::id.eq(id))
+                        .filter(password_reset_tokens::used_at.is_null()),
+                )
+                .set(password_reset_tokens::used_at.eq(now))
+                .execute(conn)?;
+
+                diesel::update(
+                    password_reset_tokens::table
+                        .filter(password_reset_tokens::user_id.eq(user_id))
+                        .filter(password_reset_tokens::used_at.is_null()),
+                )
+                .set(password_reset_tokens::used_at.eq(now))
+                .execute(conn)?;
+
+                Ok::<_, diesel::result::Error>(affected == 1)
+            })
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
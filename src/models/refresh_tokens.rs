@@ -0,0 +1,149 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::models::schema::refresh_tokens;
+use crate::utils::{error_mapper, MappedErrors};
+
+#[derive(Queryable, Selectable, Debug)]
+#[diesel(table_name = crate::models::schema::refresh_tokens)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct RefreshToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub access_jti: String,
+    pub access_expires_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = crate::models::schema::refresh_tokens)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct RefreshTokenCreate {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub access_jti: String,
+    pub access_expires_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    refresh_token: RefreshTokenCreate,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(refresh_tokens::table)
+            .values(&refresh_token)
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+pub async fn find_by_hash(
+    pool: &deadpool_diesel::mysql::Pool,
+    token_hash: String,
+) -> Result<RefreshToken, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            refresh_tokens::table
+                .filter(refresh_tokens::token_hash.eq(token_hash))
+                .select(RefreshToken::as_select())
+                .first::<RefreshToken>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Returns false when the token was already revoked, e.g. by a concurrent refresh
+pub async fn revoke(pool: &deadpool_diesel::mysql::Pool, id: i32) -> Result<bool, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let affected = conn
+        .interact(move |conn| {
+            diesel::update(
+                refresh_tokens::table
+                    .filter(refresh_tokens::id.eq(id))
+                    .filter(refresh_tokens::revoked_at.is_null()),
+            )
+            .set(refresh_tokens::revoked_at.eq(Utc::now().naive_utc()))
+            .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(affected == 1)
+}
+
+pub async fn revoke_by_access_jti(
+    pool: &deadpool_diesel::mysql::Pool,
+    access_jti: String,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::update(
+            refresh_tokens::table
+                .filter(refresh_tokens::access_jti.eq(access_jti))
+                .filter(refresh_tokens::revoked_at.is_null()),
+        )
+        .set(refresh_tokens::revoked_at.eq(Utc::now().naive_utc()))
+        .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Revokes every refresh token of the user and returns the access tokens issued with them
+// that didn't expire yet, so they can be revoked too
+pub async fn revoke_all(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+) -> Result<Vec<(String, NaiveDateTime)>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                let now = Utc::now().naive_utc();
+
+                let access_tokens = refresh_tokens::table
+                    .filter(refresh_tokens::user_id.eq(user_id))
+                    .filter(refresh_tokens::access_expires_at.gt(now))
+                    .select((
+                        refresh_tokens::access_jti,
+                        refresh_tokens::access_expires_at,
+                    ))
+                    .load::<(String, NaiveDateTime)>(conn)?;
+
+                diesel::update(
+                    refresh_tokens::table
+                        .filter(refresh_tokens::user_id.eq(user_id))
+                        .filter(refresh_tokens::revoked_at.is_null()),
+                )
+                .set(refresh_tokens::revoked_at.eq(now))
+                .execute(conn)?;
+
+                Ok::<_, diesel::result::Error>(access_tokens)
+            })
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
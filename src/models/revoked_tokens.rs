@@ -0,0 +1,49 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+use crate::models::schema::revoked_tokens;
+use crate::utils::{error_mapper, MappedErrors};
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    jti: String,
+    expires_at: NaiveDateTime,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::insert_or_ignore_into(revoked_tokens::table)
+            .values((
+                revoked_tokens::jti.eq(jti),
+                revoked_tokens::expires_at.eq(expires_at),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Drops expired entries and returns the ones still relevant
+pub async fn list_active(
+    pool: &deadpool_diesel::mysql::Pool,
+) -> Result<Vec<(String, NaiveDateTime)>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            let now = Utc::now().naive_utc();
+
+            diesel::delete(revoked_tokens::table.filter(revoked_tokens::expires_at.le(now)))
+                .execute(conn)?;
+
+            revoked_tokens::table.load::<(String, NaiveDateTime)>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
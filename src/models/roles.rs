@@ -0,0 +1,295 @@
+use std::collections::BTreeSet;
+use utoipa::ToSchema;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::auth::permissions;
+use crate::models::schema::{role_permissions, roles, users, users_roles};
+use crate::utils::{ensure_affected, error_mapper, MappedErrors};
+
+#[derive(Queryable, Selectable, Serialize, Debug, ToSchema)]
+#[diesel(table_name = crate::models::schema::roles)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct Role {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub created_at: NaiveDateTime,
+}
+
+// Used both to create and to replace a role
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct RoleData {
+    #[validate(length(min = 1, max = 50))]
+    pub name: String,
+    #[validate(length(max = 255))]
+    #[serde(default)]
+    pub description: String,
+    #[validate(custom = "crate::auth::permissions::validate_known")]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct UserRoles {
+    pub role_ids: Vec<i32>,
+}
+
+fn insert_permissions(
+    conn: &mut MysqlConnection,
+    role_id: i32,
+    permissions: &[String],
+) -> QueryResult<usize> {
+    let permissions: BTreeSet<&String> = permissions.iter().collect();
+    let rows: Vec<_> = permissions
+        .into_iter()
+        .map(|permission| {
+            (
+                role_permissions::role_id.eq(role_id),
+                role_permissions::permission.eq(permission),
+            )
+        })
+        .collect();
+
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    diesel::insert_into(role_permissions::table)
+        .values(rows)
+        .execute(conn)
+}
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    role: RoleData,
+) -> Result<i32, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let role_id = conn
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                diesel::insert_into(roles::table)
+                    .values((
+                        roles::name.eq(&role.name),
+                        roles::description.eq(&role.description),
+                    ))
+                    .execute(conn)?;
+
+                let role_id = roles::table
+                    .filter(roles::name.eq(&role.name))
+                    .select(roles::id)
+                    .first::<i32>(conn)?;
+
+                insert_permissions(conn, role_id, &role.permissions)?;
+
+                Ok::<_, diesel::result::Error>(role_id)
+            })
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(role_id)
+}
+
+pub async fn list(pool: &deadpool_diesel::mysql::Pool) -> Result<Vec<Role>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            roles::table
+                .order(roles::name)
+                .select(Role::as_select())
+                .load::<Role>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
+
+pub async fn find(pool: &deadpool_diesel::mysql::Pool, id: u32) -> Result<Role, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            roles::table
+                .find(id as i32)
+                .select(Role::as_select())
+                .first::<Role>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+pub async fn find_permissions(
+    pool: &deadpool_diesel::mysql::Pool,
+    role_id: i32,
+) -> Result<Vec<String>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            role_permissions::table
+                .filter(role_permissions::role_id.eq(role_id))
+                .order(role_permissions::permission)
+                .select(role_permissions::permission)
+                .load::<String>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
+
+// The permissions of the role are replaced by the new ones
+pub async fn update(
+    pool: &deadpool_diesel::mysql::Pool,
+    id: u32,
+    role: RoleData,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::update(roles::table.find(id as i32))
+                .set((
+                    roles::name.eq(&role.name),
+                    roles::description.eq(&role.description),
+                ))
+                .execute(conn)?;
+
+            diesel::delete(role_permissions::table.filter(role_permissions::role_id.eq(id as i32)))
+                .execute(conn)?;
+
+            insert_permissions(conn, id as i32, &role.permissions)?;
+
+            Ok::<_, diesel::result::Error>(())
+        })
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Permissions and user assignments go with the role
+pub async fn delete(pool: &deadpool_diesel::mysql::Pool, id: u32) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let rows = conn
+        .interact(move |conn| diesel::delete(roles::table.find(id as i32)).execute(conn))
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    ensure_affected(rows)
+}
+
+pub async fn find_by_user(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+) -> Result<Vec<Role>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            users_roles::table
+                .inner_join(roles::table)
+                .filter(users_roles::user_id.eq(user_id))
+                .order(roles::name)
+                .select(Role::as_select())
+                .load::<Role>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
+
+// Replaces the roles of the user
+pub async fn set_user_roles(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    mut role_ids: Vec<i32>,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+    role_ids.sort_unstable();
+    role_ids.dedup();
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(users_roles::table.filter(users_roles::user_id.eq(user_id)))
+                .execute(conn)?;
+
+            let rows: Vec<_> = role_ids
+                .iter()
+                .map(|role_id| {
+                    (
+                        users_roles::user_id.eq(user_id),
+                        users_roles::role_id.eq(role_id),
+                    )
+                })
+                .collect();
+
+            if !rows.is_empty() {
+                diesel::insert_into(users_roles::table)
+                    .values(rows)
+                    .execute(conn)?;
+            }
+
+            Ok::<_, diesel::result::Error>(())
+        })
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Union of the permissions of all the roles of the user, admins have all of them. A disabled
+// user is NotFound, like one that was deleted
+pub async fn find_permissions_by_user(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+) -> Result<Vec<String>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            let is_admin = users::table
+                .find(user_id)
+                .filter(users::is_active.eq(true))
+                .select(users::is_admin)
+                .first::<bool>(conn)?;
+
+            if is_admin {
+                return Ok(permissions::ALL.iter().map(|p| p.to_string()).collect());
+            }
+
+            users_roles::table
+                .inner_join(
+                    role_permissions::table.on(role_permissions::role_id.eq(users_roles::role_id)),
+                )
+                .filter(users_roles::user_id.eq(user_id))
+                .select(role_permissions::permission)
+                .distinct()
+                .load::<String>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
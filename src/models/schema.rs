@@ -1,5 +1,37 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_keys (id) {
+        id -> Integer,
+        #[max_length = 100]
+        name -> Varchar,
+        #[max_length = 12]
+        key_prefix -> Char,
+        #[max_length = 64]
+        key_hash -> Char,
+        #[max_length = 1024]
+        scopes -> Varchar,
+        created_by -> Nullable<Integer>,
+        expires_at -> Nullable<Datetime>,
+        last_used_at -> Nullable<Datetime>,
+        revoked_at -> Nullable<Datetime>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    credentials (id) {
+        id -> Integer,
+        user_id -> Integer,
+        #[max_length = 64]
+        badge_uid -> Varchar,
+        #[max_length = 10]
+        status -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     days_of_week (id) {
         id -> Integer,
@@ -8,6 +40,93 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    login_lockouts (user_id, ip) {
+        user_id -> Integer,
+        #[max_length = 45]
+        ip -> Varchar,
+        failed_attempts -> Integer,
+        locked_until -> Nullable<Datetime>,
+    }
+}
+
+diesel::table! {
+    password_history (id) {
+        id -> Integer,
+        user_id -> Integer,
+        #[max_length = 255]
+        password_hash -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    password_policy (id) {
+        id -> Integer,
+        min_length -> Integer,
+        require_uppercase -> Bool,
+        require_lowercase -> Bool,
+        require_digit -> Bool,
+        require_symbol -> Bool,
+        history_size -> Integer,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    password_reset_tokens (id) {
+        id -> Integer,
+        user_id -> Integer,
+        #[max_length = 64]
+        token_hash -> Char,
+        expires_at -> Datetime,
+        used_at -> Nullable<Datetime>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    refresh_tokens (id) {
+        id -> Integer,
+        user_id -> Integer,
+        #[max_length = 64]
+        token_hash -> Char,
+        #[max_length = 32]
+        access_jti -> Char,
+        access_expires_at -> Datetime,
+        expires_at -> Datetime,
+        revoked_at -> Nullable<Datetime>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    revoked_tokens (jti) {
+        #[max_length = 32]
+        jti -> Char,
+        expires_at -> Datetime,
+    }
+}
+
+diesel::table! {
+    role_permissions (role_id, permission) {
+        role_id -> Integer,
+        #[max_length = 64]
+        permission -> Varchar,
+    }
+}
+
+diesel::table! {
+    roles (id) {
+        id -> Integer,
+        #[max_length = 50]
+        name -> Varchar,
+        #[max_length = 255]
+        description -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     users (id) {
         id -> Integer,
@@ -20,6 +139,8 @@ diesel::table! {
         created_at -> Timestamp,
         is_admin -> Bool,
         is_active -> Bool,
+        failed_login_attempts -> Integer,
+        locked_until -> Nullable<Datetime>,
     }
 }
 
@@ -32,6 +153,15 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    users_duress_codes (user_id) {
+        user_id -> Integer,
+        #[max_length = 64]
+        code_hash -> Char,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     users_logs (id) {
         id -> Integer,
@@ -39,16 +169,114 @@ diesel::table! {
         #[max_length = 255]
         action -> Varchar,
         timestamp -> Nullable<Timestamp>,
+        #[max_length = 10]
+        severity -> Varchar,
+        actor_id -> Nullable<Integer>,
+    }
+}
+
+diesel::table! {
+    users_pins (id) {
+        id -> Integer,
+        user_id -> Integer,
+        #[max_length = 64]
+        pin_hash -> Char,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    users_recovery_codes (id) {
+        id -> Integer,
+        user_id -> Integer,
+        #[max_length = 64]
+        code_hash -> Char,
+        used_at -> Nullable<Datetime>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    users_roles (user_id, role_id) {
+        user_id -> Integer,
+        role_id -> Integer,
+    }
+}
+
+diesel::table! {
+    users_totp (user_id) {
+        user_id -> Integer,
+        #[max_length = 64]
+        secret -> Varchar,
+        confirmed_at -> Nullable<Datetime>,
+        last_used_step -> Nullable<Bigint>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    visits (id) {
+        id -> Integer,
+        host_user_id -> Integer,
+        #[max_length = 255]
+        guest_name -> Varchar,
+        starts_at -> Datetime,
+        ends_at -> Datetime,
+        #[max_length = 64]
+        code_hash -> Char,
+        max_uses -> Integer,
+        uses -> Integer,
+        is_cancelled -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    visits_doors (visit_id, door_id) {
+        visit_id -> Integer,
+        door_id -> Integer,
     }
 }
 
+diesel::joinable!(api_keys -> users (created_by));
+diesel::joinable!(credentials -> users (user_id));
+diesel::joinable!(login_lockouts -> users (user_id));
+diesel::joinable!(password_history -> users (user_id));
+diesel::joinable!(password_reset_tokens -> users (user_id));
+diesel::joinable!(refresh_tokens -> users (user_id));
+diesel::joinable!(role_permissions -> roles (role_id));
 diesel::joinable!(users_accesses -> days_of_week (day_of_week));
 diesel::joinable!(users_accesses -> users (user_id));
+diesel::joinable!(users_duress_codes -> users (user_id));
 diesel::joinable!(users_logs -> users (user_id));
+diesel::joinable!(users_pins -> users (user_id));
+diesel::joinable!(users_recovery_codes -> users (user_id));
+diesel::joinable!(users_roles -> roles (role_id));
+diesel::joinable!(users_roles -> users (user_id));
+diesel::joinable!(users_totp -> users (user_id));
+diesel::joinable!(visits -> users (host_user_id));
+diesel::joinable!(visits_doors -> visits (visit_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
+    credentials,
     days_of_week,
+    login_lockouts,
+    password_history,
+    password_policy,
+    password_reset_tokens,
+    refresh_tokens,
+    revoked_tokens,
+    role_permissions,
+    roles,
     users,
     users_accesses,
+    users_duress_codes,
     users_logs,
+    users_pins,
+    users_recovery_codes,
+    users_roles,
+    users_totp,
+    visits,
+    visits_doors,
 );
@@ -1,10 +1,13 @@
 use chrono::NaiveDateTime;
+use diesel::mysql::Mysql;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
 use crate::models::schema::users;
-use crate::utils::{error_mapper, MappedErrors};
+use crate::utils::pagination::{contains_pattern, PageRequest};
+use crate::utils::{ensure_affected, error_mapper, MappedErrors};
 
 #[derive(Queryable, Deserialize, Debug, Validate)]
 #[diesel(table_name =  crate::models::schema::users)]
@@ -20,29 +23,40 @@ pub struct User {
     pub created_at: NaiveDateTime,
     pub is_admin: bool,
     pub is_active: bool,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<NaiveDateTime>,
 }
 
-#[derive(Insertable, Deserialize, Debug, Clone, Validate)]
+#[derive(Insertable, Deserialize, Debug, Clone, Validate, ToSchema)]
 #[diesel(table_name =  crate::models::schema::users)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct CreateUser {
     #[validate(length(min = 3, max = 50))]
     pub username: String,
-    #[validate(length(min = 8))]
+    // Checked against the password policy instead
     pub password: String,
     #[validate(email)]
     pub email: String,
 }
 
 // Update user
-#[derive(Deserialize, Debug, Validate)]
+#[derive(Deserialize, Debug, Validate, ToSchema)]
 pub struct UpdateUser {
+    #[validate(length(min = 3, max = 50))]
     pub username: String,
     #[validate(email)]
     pub email: String,
 }
 
-#[derive(Queryable, Serialize, Deserialize, Debug)]
+// Password change by the user, the current password must be given
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct ChangePassword {
+    pub current_password: String,
+    // Checked against the password policy instead
+    pub new_password: String,
+}
+
+#[derive(Queryable, Serialize, Deserialize, Debug, ToSchema)]
 #[diesel(table_name =  crate::models::schema::users)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct ListUser {
@@ -52,30 +66,56 @@ pub struct ListUser {
     pub created_at: NaiveDateTime,
     pub is_admin: bool,
     pub is_active: bool,
+    pub failed_login_attempts: i32,
+    pub locked_until: Option<NaiveDateTime>,
+}
+
+// Everything but the password
+impl From<User> for ListUser {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            created_at: user.created_at,
+            is_admin: user.is_admin,
+            is_active: user.is_active,
+            failed_login_attempts: user.failed_login_attempts,
+            locked_until: user.locked_until,
+        }
+    }
 }
 
+define_sql_function! {
+    fn last_insert_id() -> Unsigned<BigInt>;
+}
+
+// Returns the id of the new user
 pub async fn create(
     pool: &deadpool_diesel::mysql::Pool,
     user: CreateUser,
-) -> Result<usize, MappedErrors> {
+) -> Result<i32, MappedErrors> {
     let conn = pool.get().await.map_err(error_mapper)?;
 
     let user_id = conn
         .interact(move |conn| {
-            diesel::insert_into(users::table)
-                .values((
-                    users::username.eq(user.username),
-                    users::email.eq(user.email),
-                    users::password.eq(user.password),
-                ))
-                .execute(conn)
+            conn.transaction(|conn| {
+                diesel::insert_into(users::table)
+                    .values((
+                        users::username.eq(user.username),
+                        users::email.eq(user.email),
+                        users::password.eq(user.password),
+                    ))
+                    .execute(conn)?;
+
+                diesel::select(last_insert_id()).get_result::<u64>(conn)
+            })
         })
         .await
         .map_err(error_mapper)?
-        .map_err(error_mapper)
-        .unwrap();
+        .map_err(error_mapper)?;
 
-    Ok(user_id)
+    Ok(user_id as i32)
 }
 
 pub async fn find(
@@ -90,14 +130,7 @@ pub async fn find(
         .map_err(error_mapper)?
         .map_err(error_mapper)?;
 
-    Ok(ListUser {
-        id: user.id,
-        username: user.username,
-        email: user.email,
-        created_at: user.created_at,
-        is_admin: user.is_admin,
-        is_active: user.is_active,
-    })
+    Ok(ListUser::from(user))
 }
 
 pub async fn update(
@@ -107,51 +140,157 @@ pub async fn update(
 ) -> Result<(), MappedErrors> {
     let conn = pool.get().await.map_err(error_mapper)?;
 
-    conn.interact(move |conn| {
-        diesel::update(users::table.find(user_id as i32))
-            .set((
-                users::username.eq(user.username),
-                users::email.eq(user.email),
-            ))
-            .execute(conn)
-    })
-    .await
-    .map_err(error_mapper)?
-    .map_err(error_mapper)?;
+    let rows = conn
+        .interact(move |conn| {
+            diesel::update(users::table.find(user_id as i32))
+                .set((
+                    users::username.eq(user.username),
+                    users::email.eq(user.email),
+                ))
+                .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
 
-    Ok(())
+    ensure_affected(rows)
 }
 
-pub async fn list(pool: &deadpool_diesel::mysql::Pool) -> Result<Vec<ListUser>, MappedErrors> {
+// Only active users, disabled accounts can't recover their password
+pub async fn find_active_by_email(
+    pool: &deadpool_diesel::mysql::Pool,
+    email: String,
+) -> Result<ListUser, MappedErrors> {
     let conn = pool.get().await.map_err(error_mapper)?;
 
-    let results = conn
-        .interact(move |conn| users::table.load::<User>(conn))
+    let user = conn
+        .interact(move |conn| {
+            users::table
+                .filter(users::email.eq(email))
+                .filter(users::is_active.eq(true))
+                .first::<User>(conn)
+        })
         .await
         .map_err(error_mapper)?
         .map_err(error_mapper)?;
 
-    let user_list = results
-        .into_iter()
-        .map(|user| ListUser {
-            id: user.id,
-            username: user.username,
-            email: user.email,
-            created_at: user.created_at,
-            is_admin: user.is_admin,
-            is_active: user.is_active,
+    Ok(ListUser::from(user))
+}
+
+pub async fn find_by_email(
+    pool: &deadpool_diesel::mysql::Pool,
+    email: String,
+) -> Result<User, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let user = conn
+        .interact(move |conn| {
+            users::table
+                .filter(users::email.eq(email))
+                .first::<User>(conn)
         })
-        .collect();
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
 
-    Ok(user_list)
+    Ok(user)
 }
 
-pub async fn disable(pool: &deadpool_diesel::mysql::Pool, user_id: u32) -> Result<(), MappedErrors> {
+// Counts a wrong password from any IP, once `max_attempts` is reached the account is locked
+// until `lock_until` and the counter starts over. Returns the attempts counted and if it got locked
+pub async fn register_failed_login(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    max_attempts: i32,
+    lock_until: NaiveDateTime,
+) -> Result<(i32, bool), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                diesel::update(users::table.find(user_id))
+                    .set(users::failed_login_attempts.eq(users::failed_login_attempts + 1))
+                    .execute(conn)?;
+
+                let attempts = users::table
+                    .find(user_id)
+                    .select(users::failed_login_attempts)
+                    .first::<i32>(conn)?;
+
+                if attempts < max_attempts {
+                    return Ok::<_, diesel::result::Error>((attempts, false));
+                }
+
+                diesel::update(users::table.find(user_id))
+                    .set((
+                        users::failed_login_attempts.eq(0),
+                        users::locked_until.eq(lock_until),
+                    ))
+                    .execute(conn)?;
+
+                Ok((attempts, true))
+            })
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Clears the failed attempts and the lock of the account, after a successful login or by an admin
+pub async fn unlock(pool: &deadpool_diesel::mysql::Pool, user_id: i32) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let rows = conn
+        .interact(move |conn| {
+            diesel::update(users::table.find(user_id))
+                .set((
+                    users::failed_login_attempts.eq(0),
+                    users::locked_until.eq(None::<NaiveDateTime>),
+                ))
+                .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    ensure_affected(rows)
+}
+
+pub async fn check_password(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+    password: String,
+) -> Result<bool, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let count = conn
+        .interact(move |conn| {
+            users::table
+                .filter(users::id.eq(user_id as i32))
+                .filter(users::password.eq(password))
+                .count()
+                .get_result::<i64>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(count > 0)
+}
+
+pub async fn update_password(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+    password: String,
+) -> Result<(), MappedErrors> {
     let conn = pool.get().await.map_err(error_mapper)?;
 
     conn.interact(move |conn| {
         diesel::update(users::table.find(user_id as i32))
-            .set(users::is_active.eq(false))
+            .set(users::password.eq(password))
             .execute(conn)
     })
     .await
@@ -161,6 +300,100 @@ pub async fn disable(pool: &deadpool_diesel::mysql::Pool, user_id: u32) -> Resul
     Ok(())
 }
 
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserFilter {
+    pub is_active: Option<bool>,
+    pub is_admin: Option<bool>,
+    // Part of the username or email
+    pub q: Option<String>,
+}
+
+pub const SORT_FIELDS: &[&str] = &["id", "username", "email", "created_at"];
+
+fn filtered(filter: &UserFilter) -> users::BoxedQuery<'static, Mysql> {
+    let mut query = users::table.into_boxed();
+
+    if let Some(is_active) = filter.is_active {
+        query = query.filter(users::is_active.eq(is_active));
+    }
+    if let Some(is_admin) = filter.is_admin {
+        query = query.filter(users::is_admin.eq(is_admin));
+    }
+    if let Some(q) = filter.q.as_deref().filter(|q| !q.is_empty()) {
+        let pattern = contains_pattern(q);
+        query = query.filter(
+            users::username
+                .like(pattern.clone())
+                .or(users::email.like(pattern)),
+        );
+    }
+
+    query
+}
+
+// Returns the users of the page and the total matching the filter
+pub async fn list(
+    pool: &deadpool_diesel::mysql::Pool,
+    filter: UserFilter,
+    page: PageRequest,
+) -> Result<(Vec<ListUser>, i64), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let (results, total) = conn
+        .interact(move |conn| {
+            let total = filtered(&filter).count().get_result::<i64>(conn)?;
+
+            let query = filtered(&filter);
+            let descending = page.sort.as_ref().is_some_and(|sort| sort.descending);
+            let query = match page.sort.as_ref().map(|sort| sort.field.as_str()) {
+                Some("username") if descending => query.order((users::username.desc(), users::id)),
+                Some("username") => query.order((users::username.asc(), users::id)),
+                Some("email") if descending => query.order((users::email.desc(), users::id)),
+                Some("email") => query.order((users::email.asc(), users::id)),
+                Some("created_at") if descending => {
+                    query.order((users::created_at.desc(), users::id))
+                }
+                Some("created_at") => query.order((users::created_at.asc(), users::id)),
+                _ if descending => query.order(users::id.desc()),
+                _ => query.order(users::id.asc()),
+            };
+
+            let results = query
+                .limit(page.limit)
+                .offset(page.offset)
+                .load::<User>(conn)?;
+
+            Ok::<_, diesel::result::Error>((results, total))
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    let user_list = results.into_iter().map(ListUser::from).collect();
+
+    Ok((user_list, total))
+}
+
+pub async fn disable(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let rows = conn
+        .interact(move |conn| {
+            diesel::update(users::table.find(user_id as i32))
+                .set(users::is_active.eq(false))
+                .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    ensure_affected(rows)
+}
+
 pub async fn find_by_login(
     pool: &deadpool_diesel::mysql::Pool,
     email: String,
@@ -173,6 +406,7 @@ pub async fn find_by_login(
             users::table
                 .filter(users::email.eq(email))
                 .filter(users::password.eq(password))
+                .filter(users::is_active.eq(true))
                 .first::<User>(conn)
         })
         .await
@@ -0,0 +1,114 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::schema::users_logs;
+use crate::utils::{error_mapper, MappedErrors};
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::models::schema::users_logs)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct UserLog {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub action: String,
+    pub timestamp: Option<NaiveDateTime>,
+    pub severity: String,
+    pub actor_id: Option<i32>,
+}
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: Option<i32>,
+    actor_id: Option<i32>,
+    action: String,
+    severity: Severity,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(users_logs::table)
+            .values((
+                users_logs::user_id.eq(user_id),
+                users_logs::actor_id.eq(actor_id),
+                users_logs::action.eq(action),
+                users_logs::severity.eq(severity.as_str()),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogFilter {
+    pub user_id: Option<i32>,
+    pub actor_id: Option<i32>,
+    pub severity: Option<Severity>,
+    pub limit: Option<i64>,
+}
+
+const DEFAULT_LIST_LIMIT: i64 = 100;
+const MAX_LIST_LIMIT: i64 = 500;
+
+// Most recent entries first
+pub async fn list(
+    pool: &deadpool_diesel::mysql::Pool,
+    filter: LogFilter,
+) -> Result<Vec<UserLog>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            let mut query = users_logs::table.into_boxed();
+
+            if let Some(user_id) = filter.user_id {
+                query = query.filter(users_logs::user_id.eq(user_id));
+            }
+            if let Some(actor_id) = filter.actor_id {
+                query = query.filter(users_logs::actor_id.eq(actor_id));
+            }
+            if let Some(severity) = filter.severity {
+                query = query.filter(users_logs::severity.eq(severity.as_str()));
+            }
+
+            query
+                .order(users_logs::id.desc())
+                .limit(
+                    filter
+                        .limit
+                        .unwrap_or(DEFAULT_LIST_LIMIT)
+                        .clamp(1, MAX_LIST_LIMIT),
+                )
+                .select(UserLog::as_select())
+                .load::<UserLog>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
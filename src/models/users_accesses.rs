@@ -1,13 +1,17 @@
 use chrono::{Datelike, NaiveTime, Utc};
 use chrono_tz::Brazil;
+use diesel::mysql::Mysql;
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
 
-use crate::models::schema::users_accesses;
-use crate::utils::{error_mapper, MappedErrors};
+use crate::models::schema::{days_of_week, users, users_accesses};
+use crate::utils::pagination::PageRequest;
+use crate::utils::validation::cross_field_error;
+use crate::utils::{ensure_affected, error_mapper, MappedErrors};
 
-#[derive(Insertable, Queryable, Selectable, Debug, Serialize, Deserialize)]
+#[derive(Insertable, Queryable, Selectable, Debug, Serialize, Deserialize, ToSchema)]
 #[diesel(table_name = crate::models::schema::users_accesses)]
 #[diesel(check_for_backend(diesel::mysql::Mysql))]
 pub struct UserAccess {
@@ -17,7 +21,8 @@ pub struct UserAccess {
     pub end: NaiveTime,
 }
 
-#[derive(Deserialize, Debug, Validate)]
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+#[validate(schema(function = "validate_create_window"))]
 pub struct UserAccessCreate {
     #[validate(range(min = 1, max = 7))]
     pub day_of_week: i32,
@@ -25,12 +30,30 @@ pub struct UserAccessCreate {
     pub end: NaiveTime,
 }
 
-#[derive(Deserialize, Debug, Validate)]
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+#[validate(schema(function = "validate_update_window"))]
 pub struct UserAccessUpdate {
     pub start: NaiveTime,
     pub end: NaiveTime,
 }
 
+// Windows don't cross midnight, a night shift is two windows
+fn validate_window(start: NaiveTime, end: NaiveTime) -> Result<(), ValidationError> {
+    if start < end {
+        Ok(())
+    } else {
+        Err(cross_field_error("end", "after_start"))
+    }
+}
+
+fn validate_create_window(access: &UserAccessCreate) -> Result<(), ValidationError> {
+    validate_window(access.start, access.end)
+}
+
+fn validate_update_window(access: &UserAccessUpdate) -> Result<(), ValidationError> {
+    validate_window(access.start, access.end)
+}
+
 pub async fn create(
     pool: &deadpool_diesel::mysql::Pool,
     access: UserAccess,
@@ -68,13 +91,153 @@ pub async fn find(
     Ok(results)
 }
 
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserAccessFilter {
+    pub user_id: Option<i32>,
+    pub day_of_week: Option<i32>,
+}
+
+pub const SORT_FIELDS: &[&str] = &["user_id", "day_of_week", "start", "end"];
+
+fn filtered(filter: &UserAccessFilter) -> users_accesses::BoxedQuery<'static, Mysql> {
+    let mut query = users_accesses::table.into_boxed();
+
+    if let Some(user_id) = filter.user_id {
+        query = query.filter(users_accesses::user_id.eq(user_id));
+    }
+    if let Some(day_of_week) = filter.day_of_week {
+        query = query.filter(users_accesses::day_of_week.eq(day_of_week));
+    }
+
+    query
+}
+
+// Returns the accesses of the page and the total matching the filter
 pub async fn list_all(
     pool: &deadpool_diesel::mysql::Pool,
-) -> Result<Vec<UserAccess>, MappedErrors> {
+    filter: UserAccessFilter,
+    page: PageRequest,
+) -> Result<(Vec<UserAccess>, i64), MappedErrors> {
     let conn = pool.get().await.map_err(error_mapper)?;
 
     let results = conn
-        .interact(move |conn| users_accesses::table.load::<UserAccess>(conn))
+        .interact(move |conn| {
+            let total = filtered(&filter).count().get_result::<i64>(conn)?;
+
+            // The primary key breaks ties, so pages don't overlap
+            let query = filtered(&filter);
+            let descending = page.sort.as_ref().is_some_and(|sort| sort.descending);
+            let query = match page.sort.as_ref().map(|sort| sort.field.as_str()) {
+                Some("day_of_week") if descending => {
+                    query.order((users_accesses::day_of_week.desc(), users_accesses::user_id))
+                }
+                Some("day_of_week") => {
+                    query.order((users_accesses::day_of_week.asc(), users_accesses::user_id))
+                }
+                Some("start") if descending => query.order((
+                    users_accesses::start.desc(),
+                    users_accesses::user_id,
+                    users_accesses::day_of_week,
+                )),
+                Some("start") => query.order((
+                    users_accesses::start.asc(),
+                    users_accesses::user_id,
+                    users_accesses::day_of_week,
+                )),
+                Some("end") if descending => query.order((
+                    users_accesses::end.desc(),
+                    users_accesses::user_id,
+                    users_accesses::day_of_week,
+                )),
+                Some("end") => query.order((
+                    users_accesses::end.asc(),
+                    users_accesses::user_id,
+                    users_accesses::day_of_week,
+                )),
+                _ if descending => query.order((
+                    users_accesses::user_id.desc(),
+                    users_accesses::day_of_week.desc(),
+                )),
+                _ => query.order((users_accesses::user_id, users_accesses::day_of_week)),
+            };
+
+            let results = query
+                .limit(page.limit)
+                .offset(page.offset)
+                .load::<UserAccess>(conn)?;
+
+            Ok::<_, diesel::result::Error>((results, total))
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
+
+// Weekly window of a user, as shown on the schedule report
+#[derive(Queryable, Debug, Serialize, ToSchema)]
+pub struct ScheduleEntry {
+    pub user_id: i32,
+    pub username: String,
+    // Left out for callers that can't read users
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub day_of_week: i32,
+    pub day_name: String,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Debug, Default)]
+pub struct ScheduleFilter {
+    pub day_of_week: Option<i32>,
+    // Only the windows open at this time
+    pub at: Option<NaiveTime>,
+}
+
+// Windows of the active users, by day and then by username
+pub async fn schedule_report(
+    pool: &deadpool_diesel::mysql::Pool,
+    filter: ScheduleFilter,
+) -> Result<Vec<ScheduleEntry>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            let mut query = users_accesses::table
+                .inner_join(users::table)
+                .inner_join(days_of_week::table)
+                .filter(users::is_active.eq(true))
+                .into_boxed();
+
+            if let Some(day_of_week) = filter.day_of_week {
+                query = query.filter(users_accesses::day_of_week.eq(day_of_week));
+            }
+            if let Some(at) = filter.at {
+                query = query
+                    .filter(users_accesses::start.le(at))
+                    .filter(users_accesses::end.ge(at));
+            }
+
+            query
+                .order((
+                    users_accesses::day_of_week,
+                    users::username,
+                    users_accesses::start,
+                ))
+                .select((
+                    users::id,
+                    users::username,
+                    users::email.nullable(),
+                    users_accesses::day_of_week,
+                    days_of_week::name,
+                    users_accesses::start,
+                    users_accesses::end,
+                ))
+                .load::<ScheduleEntry>(conn)
+        })
         .await
         .map_err(error_mapper)?
         .map_err(error_mapper)?;
@@ -90,19 +253,20 @@ pub async fn update(
 ) -> Result<(), MappedErrors> {
     let conn = pool.get().await.map_err(error_mapper)?;
 
-    conn.interact(move |conn| {
-        diesel::update(users_accesses::table.find((user_id as i32, day_id as i32)))
-            .set((
-                users_accesses::start.eq(user_accesses.start),
-                users_accesses::end.eq(user_accesses.end),
-            ))
-            .execute(conn)
-    })
-    .await
-    .map_err(error_mapper)?
-    .map_err(error_mapper)?;
+    let rows = conn
+        .interact(move |conn| {
+            diesel::update(users_accesses::table.find((user_id as i32, day_id as i32)))
+                .set((
+                    users_accesses::start.eq(user_accesses.start),
+                    users_accesses::end.eq(user_accesses.end),
+                ))
+                .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
 
-    Ok(())
+    ensure_affected(rows)
 }
 
 pub async fn delete(
@@ -112,14 +276,16 @@ pub async fn delete(
 ) -> Result<(), MappedErrors> {
     let conn = pool.get().await.map_err(error_mapper)?;
 
-    conn.interact(move |conn| {
-        diesel::delete(users_accesses::table.find((user_id as i32, day_id as i32))).execute(conn)
-    })
-    .await
-    .map_err(error_mapper)?
-    .map_err(error_mapper)?;
+    let rows = conn
+        .interact(move |conn| {
+            diesel::delete(users_accesses::table.find((user_id as i32, day_id as i32)))
+                .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
 
-    Ok(())
+    ensure_affected(rows)
 }
 
 pub async fn has_access_now(
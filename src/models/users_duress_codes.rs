@@ -0,0 +1,113 @@
+use diesel::prelude::*;
+
+use crate::models::schema::{users, users_duress_codes};
+use crate::utils::{ensure_affected, error_mapper, MappedErrors};
+
+// Creates or replaces the user's duress code. A code taken by another user is a Conflict, a
+// REPLACE would have deleted the other user's row instead
+pub async fn set(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+    code_hash: String,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(users_duress_codes::table.find(user_id as i32)).execute(conn)?;
+
+            diesel::insert_into(users_duress_codes::table)
+                .values((
+                    users_duress_codes::user_id.eq(user_id as i32),
+                    users_duress_codes::code_hash.eq(code_hash),
+                ))
+                .execute(conn)
+        })
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+pub async fn exists(
+    pool: &deadpool_diesel::mysql::Pool,
+    code_hash: String,
+) -> Result<bool, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            diesel::select(diesel::dsl::exists(
+                users_duress_codes::table.filter(users_duress_codes::code_hash.eq(code_hash)),
+            ))
+            .get_result::<bool>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+pub async fn find_user_by_code(
+    pool: &deadpool_diesel::mysql::Pool,
+    code_hash: String,
+) -> Result<i32, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            users_duress_codes::table
+                .inner_join(users::table)
+                .filter(users_duress_codes::code_hash.eq(code_hash))
+                .filter(users::is_active.eq(true))
+                .select(users::id)
+                .first::<i32>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Duress code typed as the password of the account, on the password unlock
+pub async fn find_user_by_email_and_code(
+    pool: &deadpool_diesel::mysql::Pool,
+    email: String,
+    code_hash: String,
+) -> Result<i32, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            users_duress_codes::table
+                .inner_join(users::table)
+                .filter(users::email.eq(email))
+                .filter(users_duress_codes::code_hash.eq(code_hash))
+                .filter(users::is_active.eq(true))
+                .select(users::id)
+                .first::<i32>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+pub async fn delete(pool: &deadpool_diesel::mysql::Pool, user_id: u32) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let rows = conn
+        .interact(move |conn| {
+            diesel::delete(users_duress_codes::table.find(user_id as i32)).execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    ensure_affected(rows)
+}
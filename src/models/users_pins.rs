@@ -0,0 +1,142 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::models::schema::{users, users_pins};
+use crate::utils::{ensure_affected, error_mapper, MappedErrors};
+
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::models::schema::users_pins)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct ListUserPin {
+    pub id: i32,
+    pub created_at: NaiveDateTime,
+}
+
+pub const MIN_PIN_DIGITS: usize = 6;
+
+// PINs are generated by the API, the client only picks the length. The PIN value itself is
+// returned once and never logged
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct UserPinCreate {
+    #[validate(range(min = 6, max = 12))]
+    pub digits: Option<usize>,
+}
+
+pub fn validate_digits(pin: &str) -> Result<(), ValidationError> {
+    if pin.chars().all(|c| c.is_ascii_digit()) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("pin_not_numeric"))
+    }
+}
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+    pin_hash: String,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::insert_into(users_pins::table)
+            .values((
+                users_pins::user_id.eq(user_id as i32),
+                users_pins::pin_hash.eq(pin_hash),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+pub async fn find(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+) -> Result<Vec<ListUserPin>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let results = conn
+        .interact(move |conn| {
+            users_pins::table
+                .filter(users_pins::user_id.eq(user_id as i32))
+                .select(ListUserPin::as_select())
+                .load::<ListUserPin>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(results)
+}
+
+pub async fn exists(
+    pool: &deadpool_diesel::mysql::Pool,
+    pin_hash: String,
+) -> Result<bool, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            diesel::select(diesel::dsl::exists(
+                users_pins::table.filter(users_pins::pin_hash.eq(pin_hash)),
+            ))
+            .get_result::<bool>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Find the owner of a PIN, only active users are able to unlock doors
+pub async fn find_user_by_pin(
+    pool: &deadpool_diesel::mysql::Pool,
+    pin_hash: String,
+) -> Result<i32, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            users_pins::table
+                .inner_join(users::table)
+                .filter(users_pins::pin_hash.eq(pin_hash))
+                .filter(users::is_active.eq(true))
+                .select(users::id)
+                .first::<i32>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+pub async fn delete(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: u32,
+    pin_id: u32,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let rows = conn
+        .interact(move |conn| {
+            diesel::delete(
+                users_pins::table
+                    .filter(users_pins::id.eq(pin_id as i32))
+                    .filter(users_pins::user_id.eq(user_id as i32)),
+            )
+            .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    ensure_affected(rows)
+}
@@ -0,0 +1,215 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Deserialize;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::models::schema::{users_recovery_codes, users_totp};
+use crate::utils::{error_mapper, MappedErrors};
+
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = crate::models::schema::users_totp)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct UserTotp {
+    pub user_id: i32,
+    pub secret: String,
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub last_used_step: Option<i64>,
+}
+
+// Debug is not derived on purpose, codes must never end up in the logs
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct TotpCode {
+    #[validate(
+        length(equal = 6),
+        custom = "crate::models::users_pins::validate_digits"
+    )]
+    pub code: String,
+}
+
+pub async fn find(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+) -> Result<UserTotp, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            users_totp::table
+                .find(user_id)
+                .select(UserTotp::as_select())
+                .first::<UserTotp>(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
+
+// Stores a new unconfirmed secret, replacing any enrollment that wasn't confirmed
+pub async fn start_enrollment(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    secret: String,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        diesel::replace_into(users_totp::table)
+            .values((
+                users_totp::user_id.eq(user_id),
+                users_totp::secret.eq(secret),
+            ))
+            .execute(conn)
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+// Records the step of the code just used, returns false when a code of that step (or a later
+// one) was already used, e.g. by a concurrent login
+pub async fn use_step(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    step: i64,
+) -> Result<bool, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let affected = conn
+        .interact(move |conn| {
+            diesel::update(
+                users_totp::table.find(user_id).filter(
+                    users_totp::last_used_step
+                        .is_null()
+                        .or(users_totp::last_used_step.lt(step)),
+                ),
+            )
+            .set(users_totp::last_used_step.eq(step))
+            .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(affected == 1)
+}
+
+// Confirms the enrollment and replaces the recovery codes of the user
+pub async fn confirm(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    recovery_code_hashes: Vec<String>,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::update(users_totp::table.find(user_id))
+                .set(users_totp::confirmed_at.eq(Utc::now().naive_utc()))
+                .execute(conn)?;
+
+            replace_recovery_codes(conn, user_id, recovery_code_hashes)?;
+
+            Ok::<_, diesel::result::Error>(())
+        })
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+pub async fn regenerate_recovery_codes(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    recovery_code_hashes: Vec<String>,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| replace_recovery_codes(conn, user_id, recovery_code_hashes))
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
+
+fn replace_recovery_codes(
+    conn: &mut MysqlConnection,
+    user_id: i32,
+    recovery_code_hashes: Vec<String>,
+) -> Result<(), diesel::result::Error> {
+    diesel::delete(users_recovery_codes::table.filter(users_recovery_codes::user_id.eq(user_id)))
+        .execute(conn)?;
+
+    let rows: Vec<_> = recovery_code_hashes
+        .into_iter()
+        .map(|code_hash| {
+            (
+                users_recovery_codes::user_id.eq(user_id),
+                users_recovery_codes::code_hash.eq(code_hash),
+            )
+        })
+        .collect();
+
+    diesel::insert_into(users_recovery_codes::table)
+        .values(&rows)
+        .execute(conn)?;
+
+    Ok(())
+}
+
+// Returns false when the code doesn't exist or was already used
+pub async fn use_recovery_code(
+    pool: &deadpool_diesel::mysql::Pool,
+    user_id: i32,
+    code_hash: String,
+) -> Result<bool, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let affected = conn
+        .interact(move |conn| {
+            diesel::update(
+                users_recovery_codes::table
+                    .filter(users_recovery_codes::user_id.eq(user_id))
+                    .filter(users_recovery_codes::code_hash.eq(code_hash))
+                    .filter(users_recovery_codes::used_at.is_null()),
+            )
+            .set(users_recovery_codes::used_at.eq(Utc::now().naive_utc()))
+            .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(affected > 0)
+}
+
+// Removes the second factor along with the recovery codes
+pub async fn delete(pool: &deadpool_diesel::mysql::Pool, user_id: i32) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    conn.interact(move |conn| {
+        conn.transaction(|conn| {
+            diesel::delete(
+                users_recovery_codes::table.filter(users_recovery_codes::user_id.eq(user_id)),
+            )
+            .execute(conn)?;
+            diesel::delete(users_totp::table.find(user_id)).execute(conn)?;
+
+            Ok::<_, diesel::result::Error>(())
+        })
+    })
+    .await
+    .map_err(error_mapper)?
+    .map_err(error_mapper)?;
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+use crate::models::schema::{users, visits, visits_doors};
+use crate::utils::validation::cross_field_error;
+use crate::utils::{ensure_affected, error_mapper, MappedErrors};
+
+#[derive(Queryable, Selectable, Debug, Serialize, ToSchema)]
+#[diesel(table_name = crate::models::schema::visits)]
+#[diesel(check_for_backend(diesel::mysql::Mysql))]
+pub struct Visit {
+    pub id: i32,
+    pub host_user_id: i32,
+    pub guest_name: String,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub is_cancelled: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VisitWithDoors {
+    #[serde(flatten)]
+    pub visit: Visit,
+    pub doors: Vec<i32>,
+}
+
+// Times are in the building local time, same as the users accesses
+#[derive(Deserialize, Debug, Clone, Validate, ToSchema)]
+#[validate(schema(function = "validate_period"))]
+pub struct VisitCreate {
+    #[validate(length(min = 1, max = 255))]
+    pub guest_name: String,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    #[validate(range(min = 1, max = 100))]
+    pub max_uses: Option<i32>,
+    #[validate(length(min = 1))]
+    pub doors: Vec<u32>,
+}
+
+fn validate_period(visit: &VisitCreate) -> Result<(), ValidationError> {
+    if visit.starts_at < visit.ends_at {
+        Ok(())
+    } else {
+        Err(cross_field_error("ends_at", "after_start"))
+    }
+}
+
+pub async fn create(
+    pool: &deadpool_diesel::mysql::Pool,
+    host_user_id: u32,
+    visit: VisitCreate,
+    code_hash: String,
+) -> Result<i32, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let visit_id = conn
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                diesel::insert_into(visits::table)
+                    .values((
+                        visits::host_user_id.eq(host_user_id as i32),
+                        visits::guest_name.eq(visit.guest_name),
+                        visits::starts_at.eq(visit.starts_at),
+                        visits::ends_at.eq(visit.ends_at),
+                        visits::code_hash.eq(code_hash.clone()),
+                        visits::max_uses.eq(visit.max_uses.unwrap_or(1)),
+                    ))
+                    .execute(conn)?;
+
+                let visit_id = visits::table
+                    .filter(visits::code_hash.eq(code_hash))
+                    .select(visits::id)
+                    .first::<i32>(conn)?;
+
+                let doors: Vec<_> = visit
+                    .doors
+                    .iter()
+                    .map(|door_id| {
+                        (
+                            visits_doors::visit_id.eq(visit_id),
+                            visits_doors::door_id.eq(*door_id as i32),
+                        )
+                    })
+                    .collect();
+
+                diesel::insert_or_ignore_into(visits_doors::table)
+                    .values(doors)
+                    .execute(conn)?;
+
+                Ok::<_, diesel::result::Error>(visit_id)
+            })
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(visit_id)
+}
+
+pub async fn find_by_host(
+    pool: &deadpool_diesel::mysql::Pool,
+    host_user_id: u32,
+) -> Result<Vec<VisitWithDoors>, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let (visits, doors) = conn
+        .interact(move |conn| {
+            let visits = visits::table
+                .filter(visits::host_user_id.eq(host_user_id as i32))
+                .order(visits::starts_at.desc())
+                .select(Visit::as_select())
+                .load::<Visit>(conn)?;
+
+            let visit_ids: Vec<i32> = visits.iter().map(|visit| visit.id).collect();
+            let doors = visits_doors::table
+                .filter(visits_doors::visit_id.eq_any(visit_ids))
+                .load::<(i32, i32)>(conn)?;
+
+            Ok::<_, diesel::result::Error>((visits, doors))
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    let results = visits
+        .into_iter()
+        .map(|visit| {
+            let visit_doors = doors
+                .iter()
+                .filter(|(visit_id, _)| *visit_id == visit.id)
+                .map(|(_, door_id)| *door_id)
+                .collect();
+
+            VisitWithDoors {
+                visit,
+                doors: visit_doors,
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+pub async fn cancel(
+    pool: &deadpool_diesel::mysql::Pool,
+    host_user_id: u32,
+    visit_id: u32,
+) -> Result<(), MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let rows = conn
+        .interact(move |conn| {
+            diesel::update(
+                visits::table
+                    .filter(visits::id.eq(visit_id as i32))
+                    .filter(visits::host_user_id.eq(host_user_id as i32)),
+            )
+            .set(visits::is_cancelled.eq(true))
+            .execute(conn)
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    ensure_affected(rows)
+}
+
+// Consumes one use of the visit code on the given door, returns NotFound when the code
+// doesn't exist, is cancelled, expired, used up or not valid for that door
+pub async fn use_code(
+    pool: &deadpool_diesel::mysql::Pool,
+    code_hash: String,
+    door_id: u32,
+    now: NaiveDateTime,
+) -> Result<Visit, MappedErrors> {
+    let conn = pool.get().await.map_err(error_mapper)?;
+
+    let result = conn
+        .interact(move |conn| {
+            conn.transaction(|conn| {
+                let visit = visits::table
+                    .inner_join(visits_doors::table)
+                    .inner_join(users::table)
+                    .filter(visits::code_hash.eq(code_hash))
+                    .filter(visits_doors::door_id.eq(door_id as i32))
+                    .filter(visits::is_cancelled.eq(false))
+                    .filter(visits::starts_at.le(now))
+                    .filter(visits::ends_at.ge(now))
+                    .filter(visits::uses.lt(visits::max_uses))
+                    // The visit ends with the host's account
+                    .filter(users::is_active.eq(true))
+                    .select(Visit::as_select())
+                    .for_update()
+                    .first::<Visit>(conn)?;
+
+                diesel::update(visits::table.find(visit.id))
+                    .set(visits::uses.eq(visits::uses + 1))
+                    .execute(conn)?;
+
+                Ok::<_, diesel::result::Error>(visit)
+            })
+        })
+        .await
+        .map_err(error_mapper)?
+        .map_err(error_mapper)?;
+
+    Ok(result)
+}
@@ -0,0 +1,321 @@
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+use crate::controllers::{
+    api_keys, auth, credentials, door, door_tokens, logs, me, oidc, password_policy,
+    password_reset, roles, service_alive, totp, user_accesses, users, users_duress_codes,
+    users_pins, visits,
+};
+use crate::models;
+use crate::utils::{errors, pagination, Response};
+
+// OpenAPI 3 document of the API, built from the `#[utoipa::path]` of the handlers. Every route
+// has to be listed here, the test below fails otherwise
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Access Control API"),
+    servers((url = "/api/v1")),
+    paths(
+        service_alive::alive_route,
+        auth::login,
+        auth::refresh,
+        auth::logout,
+        auth::logout_all,
+        auth::jwks,
+        totp::login_totp,
+        totp::login_enroll,
+        totp::enroll,
+        totp::confirm,
+        totp::disable,
+        totp::regenerate_recovery_codes,
+        oidc::start_login,
+        oidc::callback,
+        password_reset::request_reset,
+        password_reset::confirm_reset,
+        me::find_me,
+        me::update_me,
+        me::change_password,
+        me::find_my_accesses,
+        users::create_user,
+        users::find_user,
+        users::list_all,
+        users::update_user,
+        users::delete_user,
+        users::unlock_user,
+        user_accesses::create_access,
+        user_accesses::find_by_user,
+        user_accesses::list_all,
+        user_accesses::update_access,
+        user_accesses::delete_access,
+        user_accesses::schedule_report,
+        users_pins::create_pin,
+        users_pins::find_by_user,
+        users_pins::delete_pin,
+        users_duress_codes::set_duress_code,
+        users_duress_codes::delete_duress_code,
+        credentials::create_credential,
+        credentials::find_by_user,
+        credentials::update_credential,
+        credentials::revoke_credential,
+        visits::create_visit,
+        visits::find_by_host,
+        visits::cancel_visit,
+        door::unlock,
+        door::unlock_pin,
+        door::unlock_visit,
+        door::unlock_badge,
+        door_tokens::qr_code,
+        door_tokens::public_key,
+        password_policy::find_policy,
+        password_policy::update_policy,
+        api_keys::create_api_key,
+        api_keys::list_all,
+        api_keys::find_api_key,
+        api_keys::update_api_key,
+        api_keys::revoke_api_key,
+        roles::create_role,
+        roles::list_all,
+        roles::find_role,
+        roles::update_role,
+        roles::delete_role,
+        roles::find_user_roles,
+        roles::set_user_roles,
+        logs::list_all,
+    ),
+    components(schemas(
+        Response,
+        errors::Problem,
+        errors::FieldError,
+        pagination::UsersPage,
+        pagination::AccessesPage,
+        auth::LoginRequest,
+        auth::LoginResponse,
+        auth::MfaChallenge,
+        auth::LoginResult,
+        auth::RefreshRequest,
+        totp::MfaTokenRequest,
+        totp::TotpLoginRequest,
+        totp::TotpLoginResponse,
+        totp::TotpEnrollmentResponse,
+        totp::RecoveryCodesResponse,
+        password_reset::ResetRequest,
+        password_reset::ResetConfirm,
+        me::MyAccessesResponse,
+        user_accesses::AccessesListResponse,
+        user_accesses::ReportFormat,
+        user_accesses::ScheduleReportResponse,
+        users_pins::PinsListResponse,
+        users_pins::PinCreatedResponse,
+        users_duress_codes::DuressCodeResponse,
+        credentials::CredentialsListResponse,
+        visits::VisitsListResponse,
+        visits::VisitCreatedResponse,
+        door::UserAuth,
+        door::PinAuth,
+        door::BadgeAuth,
+        door::VisitAuth,
+        door_tokens::QrFormat,
+        door_tokens::PublicKeyResponse,
+        api_keys::ApiKeyResponse,
+        api_keys::ApiKeysListResponse,
+        api_keys::ApiKeyCreatedResponse,
+        roles::RoleResponse,
+        roles::RolesListResponse,
+        logs::LogsListResponse,
+        models::user::CreateUser,
+        models::user::UpdateUser,
+        models::user::ChangePassword,
+        models::user::ListUser,
+        models::users_accesses::UserAccess,
+        models::users_accesses::UserAccessCreate,
+        models::users_accesses::UserAccessUpdate,
+        models::users_accesses::ScheduleEntry,
+        models::users_pins::ListUserPin,
+        models::users_pins::UserPinCreate,
+        models::users_totp::TotpCode,
+        models::credentials::BadgeStatus,
+        models::credentials::Credential,
+        models::credentials::CredentialCreate,
+        models::credentials::CredentialUpdate,
+        models::visits::Visit,
+        models::visits::VisitWithDoors,
+        models::visits::VisitCreate,
+        models::password_policy::PasswordPolicy,
+        models::password_policy::PasswordPolicyUpdate,
+        models::api_keys::ApiKeyCreate,
+        models::api_keys::ApiKeyUpdate,
+        models::roles::Role,
+        models::roles::RoleData,
+        models::roles::UserRoles,
+        models::user_log::Severity,
+        models::user_log::UserLog,
+    )),
+    modifiers(&Security),
+    tags(
+        (name = "service", description = "Health check"),
+        (name = "auth", description = "Login, tokens and password reset"),
+        (name = "me", description = "Account of the logged in user"),
+        (name = "users", description = "Users"),
+        (name = "schedules", description = "Weekly access windows of the users"),
+        (name = "credentials", description = "PINs, duress codes and badges"),
+        (name = "visits", description = "Visits and their one time codes"),
+        (name = "doors", description = "Door unlocking, used by the door controllers"),
+        (name = "settings", description = "Password policy"),
+        (name = "api-keys", description = "Keys of the integrations"),
+        (name = "roles", description = "Roles and their permissions"),
+        (name = "logs", description = "Audit log"),
+    )
+)]
+pub struct ApiDoc;
+
+// Credentials accepted by the auth middleware, see `middlewares::auth::credential`. The
+// operations that need them can also fail before reaching the handler
+struct Security;
+
+impl Modify for Security {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+            );
+        }
+
+        let operations = openapi
+            .paths
+            .paths
+            .values_mut()
+            .flat_map(|item| item.operations.values_mut())
+            .filter(|operation| operation.security.is_some());
+
+        for operation in operations {
+            for (status, description) in [
+                ("401", "Missing or invalid credentials"),
+                ("403", "Missing permission"),
+            ] {
+                operation
+                    .responses
+                    .responses
+                    .entry(status.to_string())
+                    .or_insert_with(|| {
+                        ResponseBuilder::new()
+                            .description(description)
+                            .content(
+                                "application/problem+json",
+                                ContentBuilder::new()
+                                    .schema(Ref::from_schema_name("Problem"))
+                                    .build(),
+                            )
+                            .build()
+                            .into()
+                    });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+    use utoipa::openapi::PathItemType;
+    use utoipa::OpenApi;
+
+    use super::ApiDoc;
+    use crate::routes;
+
+    // Routes that serve the document and its viewer
+    const UNDOCUMENTED: &[&str] = &["/openapi.json", "/docs", "/docs/{file}"];
+
+    // (path, method) of every route served under v1, with `:param` as `{param}`
+    fn routes() -> Vec<(String, Method)> {
+        routes::v1_table()
+            .into_iter()
+            .map(|(method, path)| {
+                let path = path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(param) => format!("{{{}}}", param),
+                        None => segment.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                (path, method)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_route_is_documented() {
+        let openapi = ApiDoc::openapi();
+        let routes = routes();
+        assert!(!routes.is_empty());
+
+        let missing = routes
+            .iter()
+            .filter(|(path, _)| !UNDOCUMENTED.contains(&path.as_str()))
+            .filter(|(path, method)| {
+                let item_type = match *method {
+                    Method::GET => PathItemType::Get,
+                    Method::POST => PathItemType::Post,
+                    Method::PUT => PathItemType::Put,
+                    Method::DELETE => PathItemType::Delete,
+                    _ => PathItemType::Patch,
+                };
+
+                !openapi
+                    .paths
+                    .paths
+                    .get(path)
+                    .is_some_and(|item| item.operations.contains_key(&item_type))
+            })
+            .collect::<Vec<_>>();
+
+        assert!(
+            missing.is_empty(),
+            "Routes missing from the OpenAPI document: {:?}",
+            missing
+        );
+    }
+
+    #[test]
+    fn every_documented_operation_is_served() {
+        let routes = routes();
+
+        let missing = ApiDoc::openapi()
+            .paths
+            .paths
+            .iter()
+            .flat_map(|(path, item)| {
+                item.operations.keys().map(move |item_type| {
+                    let method = match item_type {
+                        PathItemType::Get => Method::GET,
+                        PathItemType::Post => Method::POST,
+                        PathItemType::Put => Method::PUT,
+                        PathItemType::Delete => Method::DELETE,
+                        _ => Method::PATCH,
+                    };
+
+                    (path.clone(), method)
+                })
+            })
+            .filter(|operation| !routes.contains(operation))
+            .collect::<Vec<_>>();
+
+        assert!(
+            missing.is_empty(),
+            "Documented operations not served: {:?}",
+            missing
+        );
+    }
+}
@@ -1,47 +1,383 @@
-use axum::routing::{delete, get, post, put};
+use axum::extract::State;
+use axum::handler::Handler;
+use axum::http::Method;
+use axum::routing::{on, MethodFilter, MethodRouter};
 use axum::{middleware, Router};
 
+use crate::auth::permissions;
+use crate::controllers::api_keys;
+use crate::controllers::credentials;
+use crate::controllers::door_tokens;
+use crate::controllers::logs;
+use crate::controllers::me;
+use crate::controllers::oidc;
+use crate::controllers::password_policy;
+use crate::controllers::password_reset;
+use crate::controllers::roles;
+use crate::controllers::totp;
 use crate::controllers::user_accesses;
 use crate::controllers::users;
+use crate::controllers::users_duress_codes;
+use crate::controllers::users_pins;
+use crate::controllers::visits;
 use crate::{controllers, middlewares, AppState};
 
+pub const V1_PREFIX: &str = "/api/v1";
+
+// Method and path of a route with its handler. The router is built from these lists, which
+// also tell the OpenAPI tests what is served
+type Route = (Method, &'static str, MethodRouter<AppState>);
+
+fn route<H, T>(method: Method, path: &'static str, handler: H) -> Route
+where
+    H: Handler<T, AppState>,
+    T: 'static,
+{
+    let filter = MethodFilter::try_from(method.clone()).expect("Expect a routable method");
+
+    (method, path, on(filter, handler))
+}
+
+fn router(routes: Vec<Route>) -> Router<AppState> {
+    routes
+        .into_iter()
+        .fold(Router::new(), |router, (_, path, method_router)| {
+            router.route(path, method_router)
+        })
+}
+
+// Every route of v1 with its method, paths with `:param` like in the router
+#[cfg(test)]
+pub fn v1_table() -> Vec<(Method, &'static str)> {
+    let closed = permission_routes()
+        .into_iter()
+        .flat_map(|(routes, _)| routes);
+
+    session_routes()
+        .into_iter()
+        .chain(closed)
+        .chain(open_routes())
+        .chain(docs_routes())
+        .map(|(method, path, _)| (method, path))
+        .collect()
+}
+
+// Each version of the API is its own router under its prefix, a `/api/v2` goes next to v1.
+// The unversioned paths are v1 too, kept for the clients that haven't moved yet
 pub fn builder(state: AppState) -> Router {
+    Router::new()
+        .nest(V1_PREFIX, v1_routes(state.clone()))
+        .merge(
+            v1_routes(state.clone())
+                .layer(middleware::from_fn(middlewares::deprecation::legacy_alias)),
+        )
+        .layer(middleware::from_fn(middlewares::request_context::handle))
+}
+
+fn v1_routes(state: AppState) -> Router {
     Router::new()
         .merge(closed_routes(state.clone()))
-        .route_layer(middleware::from_fn(middlewares::auth::intercept_request))
-        .merge(open_routes(state.clone()))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            middlewares::auth::intercept_request,
+        ))
+        .merge(router(open_routes()).with_state(state.clone()))
+        .merge(router(docs_routes()).with_state(state))
+}
+
+// Every route of the router needs the permission
+fn require(
+    state: &AppState,
+    router: Router<AppState>,
+    permission: &'static str,
+) -> Router<AppState> {
+    router.route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        move |State(state): State<AppState>, request, next| {
+            middlewares::permissions::require(state, permission, request, next)
+        },
+    ))
 }
 
 fn closed_routes(state: AppState) -> Router {
-    Router::new()
-        .route("/user", post(users::create_user))
-        .route("/user/:id", get(users::find_user))
-        .route("/user", get(users::list_all))
-        .route("/user/:id", put(users::update_user))
-        .route("/user/:id", delete(users::delete_user))
-        .route(
+    permission_routes()
+        .into_iter()
+        .fold(router(session_routes()), |closed, (routes, permission)| {
+            closed.merge(require(&state, router(routes), permission))
+        })
+        .with_state(state)
+}
+
+// Routes behind the login, in groups by the permission they need
+fn permission_routes() -> Vec<(Vec<Route>, &'static str)> {
+    vec![
+        (users_read_routes(), permissions::USERS_READ),
+        (users_write_routes(), permissions::USERS_WRITE),
+        (schedules_read_routes(), permissions::SCHEDULES_READ),
+        (schedules_write_routes(), permissions::SCHEDULES_WRITE),
+        (credentials_read_routes(), permissions::CREDENTIALS_READ),
+        (credentials_write_routes(), permissions::CREDENTIALS_WRITE),
+        (visits_read_routes(), permissions::VISITS_READ),
+        (visits_write_routes(), permissions::VISITS_WRITE),
+        (door_routes(), permissions::DOORS_UNLOCK_REMOTE),
+        (settings_read_routes(), permissions::SETTINGS_READ),
+        (settings_write_routes(), permissions::SETTINGS_WRITE),
+        (api_keys_routes(), permissions::API_KEYS_MANAGE),
+        (logs_routes(), permissions::LOGS_READ),
+        (roles_routes(), permissions::ROLES_MANAGE),
+    ]
+}
+
+// Routes of the logged in user, API keys are refused by the `CurrentUser` extractor
+fn session_routes() -> Vec<Route> {
+    vec![
+        route(Method::POST, "/logout", controllers::auth::logout),
+        route(Method::POST, "/logout-all", controllers::auth::logout_all),
+        route(Method::GET, "/me", me::find_me),
+        route(Method::PUT, "/me", me::update_me),
+        route(Method::PUT, "/me/password", me::change_password),
+        route(Method::GET, "/me/accesses", me::find_my_accesses),
+        route(Method::POST, "/me/totp", totp::enroll),
+        route(Method::DELETE, "/me/totp", totp::disable),
+        route(Method::POST, "/me/totp/confirm", totp::confirm),
+        route(
+            Method::POST,
+            "/me/totp/recovery-codes",
+            totp::regenerate_recovery_codes,
+        ),
+    ]
+}
+
+fn users_read_routes() -> Vec<Route> {
+    vec![
+        route(Method::GET, "/user/:id", users::find_user),
+        route(Method::GET, "/user", users::list_all),
+    ]
+}
+
+fn users_write_routes() -> Vec<Route> {
+    vec![
+        route(Method::POST, "/user", users::create_user),
+        route(Method::PUT, "/user/:id", users::update_user),
+        route(Method::DELETE, "/user/:id", users::delete_user),
+        route(Method::POST, "/user/:id/unlock", users::unlock_user),
+    ]
+}
+
+fn schedules_read_routes() -> Vec<Route> {
+    vec![
+        route(Method::GET, "/user-access", user_accesses::list_all),
+        route(
+            Method::GET,
             "/user/:user_id/user-access",
-            post(user_accesses::create_access),
-        )
-        .route(
+            user_accesses::find_by_user,
+        ),
+        route(
+            Method::GET,
+            "/reports/schedules",
+            user_accesses::schedule_report,
+        ),
+    ]
+}
+
+fn schedules_write_routes() -> Vec<Route> {
+    vec![
+        route(
+            Method::POST,
             "/user/:user_id/user-access",
-            get(user_accesses::find_by_user),
-        )
-        .route(
+            user_accesses::create_access,
+        ),
+        route(
+            Method::DELETE,
             "/user/:user_id/user-access/:day_id",
-            delete(user_accesses::delete_access),
-        )
-        .route(
+            user_accesses::delete_access,
+        ),
+        route(
+            Method::PUT,
             "/user/:user_id/user-access/:day_id",
-            put(user_accesses::update_access),
-        )
-        .with_state(state)
+            user_accesses::update_access,
+        ),
+    ]
 }
 
-fn open_routes(state: AppState) -> Router {
-    Router::new()
-        .route("/", get(controllers::service_alive::alive_route))
-        .route("/validate-password", post(controllers::door::unlock))
-        .route("/login", post(controllers::auth::login))
-        .with_state(state)
+fn credentials_read_routes() -> Vec<Route> {
+    vec![
+        route(Method::GET, "/user/:user_id/pin", users_pins::find_by_user),
+        route(
+            Method::GET,
+            "/user/:user_id/badge",
+            credentials::find_by_user,
+        ),
+    ]
+}
+
+fn credentials_write_routes() -> Vec<Route> {
+    vec![
+        route(Method::POST, "/user/:user_id/pin", users_pins::create_pin),
+        route(
+            Method::DELETE,
+            "/user/:user_id/pin/:pin_id",
+            users_pins::delete_pin,
+        ),
+        route(
+            Method::PUT,
+            "/user/:user_id/duress",
+            users_duress_codes::set_duress_code,
+        ),
+        route(
+            Method::DELETE,
+            "/user/:user_id/duress",
+            users_duress_codes::delete_duress_code,
+        ),
+        route(
+            Method::POST,
+            "/user/:user_id/badge",
+            credentials::create_credential,
+        ),
+        route(
+            Method::PUT,
+            "/user/:user_id/badge/:badge_id",
+            credentials::update_credential,
+        ),
+        route(
+            Method::DELETE,
+            "/user/:user_id/badge/:badge_id",
+            credentials::revoke_credential,
+        ),
+    ]
+}
+
+fn visits_read_routes() -> Vec<Route> {
+    vec![route(
+        Method::GET,
+        "/user/:user_id/visit",
+        visits::find_by_host,
+    )]
+}
+
+fn visits_write_routes() -> Vec<Route> {
+    vec![
+        route(Method::POST, "/user/:user_id/visit", visits::create_visit),
+        route(
+            Method::DELETE,
+            "/user/:user_id/visit/:visit_id",
+            visits::cancel_visit,
+        ),
+    ]
+}
+
+fn door_routes() -> Vec<Route> {
+    vec![route(
+        Method::GET,
+        "/user/:user_id/door/:door_id/qr",
+        door_tokens::qr_code,
+    )]
+}
+
+fn settings_read_routes() -> Vec<Route> {
+    vec![route(
+        Method::GET,
+        "/password-policy",
+        password_policy::find_policy,
+    )]
+}
+
+fn settings_write_routes() -> Vec<Route> {
+    vec![route(
+        Method::PUT,
+        "/password-policy",
+        password_policy::update_policy,
+    )]
+}
+
+fn api_keys_routes() -> Vec<Route> {
+    vec![
+        route(Method::POST, "/api-keys", api_keys::create_api_key),
+        route(Method::GET, "/api-keys", api_keys::list_all),
+        route(Method::GET, "/api-keys/:id", api_keys::find_api_key),
+        route(Method::PUT, "/api-keys/:id", api_keys::update_api_key),
+        route(Method::DELETE, "/api-keys/:id", api_keys::revoke_api_key),
+    ]
+}
+
+fn logs_routes() -> Vec<Route> {
+    vec![route(Method::GET, "/logs", logs::list_all)]
+}
+
+fn roles_routes() -> Vec<Route> {
+    vec![
+        route(Method::POST, "/roles", roles::create_role),
+        route(Method::GET, "/roles", roles::list_all),
+        route(Method::GET, "/roles/:id", roles::find_role),
+        route(Method::PUT, "/roles/:id", roles::update_role),
+        route(Method::DELETE, "/roles/:id", roles::delete_role),
+        route(Method::GET, "/user/:id/roles", roles::find_user_roles),
+        route(Method::PUT, "/user/:id/roles", roles::set_user_roles),
+    ]
+}
+
+fn open_routes() -> Vec<Route> {
+    vec![
+        route(Method::GET, "/", controllers::service_alive::alive_route),
+        route(
+            Method::POST,
+            "/validate-password",
+            controllers::door::unlock,
+        ),
+        route(
+            Method::POST,
+            "/doors/:id/unlock/pin",
+            controllers::door::unlock_pin,
+        ),
+        route(
+            Method::POST,
+            "/doors/:id/unlock/visit",
+            controllers::door::unlock_visit,
+        ),
+        route(
+            Method::POST,
+            "/validate-badge",
+            controllers::door::unlock_badge,
+        ),
+        route(Method::GET, "/doors/token-key", door_tokens::public_key),
+        route(Method::POST, "/login", controllers::auth::login),
+        route(Method::POST, "/login/totp", totp::login_totp),
+        route(Method::POST, "/login/totp/enroll", totp::login_enroll),
+        route(Method::GET, "/login/oidc", oidc::start_login),
+        route(Method::GET, "/login/oidc/callback", oidc::callback),
+        route(Method::POST, "/refresh", controllers::auth::refresh),
+        route(
+            Method::POST,
+            "/password-reset/request",
+            password_reset::request_reset,
+        ),
+        route(
+            Method::POST,
+            "/password-reset/confirm",
+            password_reset::confirm_reset,
+        ),
+        route(
+            Method::GET,
+            "/.well-known/jwks.json",
+            controllers::auth::jwks,
+        ),
+    ]
+}
+
+// The document describes v1, the unversioned `/openapi.json` and `/docs` are deprecated like
+// the other aliases
+fn docs_routes() -> Vec<Route> {
+    vec![
+        route(
+            Method::GET,
+            "/openapi.json",
+            controllers::docs::openapi_json,
+        ),
+        route(Method::GET, "/docs", controllers::docs::swagger_ui),
+        route(
+            Method::GET,
+            "/docs/:file",
+            controllers::docs::swagger_ui_file,
+        ),
+    ]
 }
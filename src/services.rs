@@ -1,2 +1,4 @@
+pub mod mailer;
 pub mod mqtt;
 pub mod sql;
+pub mod throttle;
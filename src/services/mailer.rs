@@ -0,0 +1,22 @@
+mod file_mailer;
+mod mailer_connector;
+mod smtp_mailer;
+
+use async_trait::async_trait;
+
+pub use file_mailer::FileMailer;
+pub use mailer_connector::init_mailer;
+pub use smtp_mailer::SmtpMailer;
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// Outgoing mail, the implementation is picked on startup by `init_mailer`
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), String>;
+}
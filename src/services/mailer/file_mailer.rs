@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+
+use super::smtp_mailer::build_message;
+use super::{Mail, Mailer};
+
+// Writes every mail as an .eml file, for offline development
+pub struct FileMailer {
+    transport: AsyncFileTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: String, from: Option<Mailbox>) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| format!("Unable to create mail directory {}: {}", dir, err))?;
+
+        let from = match from {
+            Some(from) => from,
+            None => "no-reply@localhost"
+                .parse::<Mailbox>()
+                .map_err(|err| format!("Invalid sender: {}", err))?,
+        };
+
+        Ok(Self {
+            transport: AsyncFileTransport::new(dir),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> Result<(), String> {
+        let message = build_message(&self.from, mail)?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|err| format!("Unable to write mail: {}", err))
+    }
+}
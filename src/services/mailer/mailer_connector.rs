@@ -0,0 +1,22 @@
+use std::sync::Arc;
+
+use super::{FileMailer, Mailer, SmtpMailer};
+use crate::config::MailConfig;
+
+// SMTP when GCA_SMTP_HOST is set, otherwise the mails are written to GCA_MAIL_DIR. The
+// configuration is refused without either, mails must reach someone
+pub fn init_mailer(config: &MailConfig) -> Arc<dyn Mailer> {
+    if let (Some(smtp), Some(from)) = (&config.smtp, &config.from) {
+        return Arc::new(
+            SmtpMailer::new(smtp, from.clone()).unwrap_or_else(|err| panic!("{}", err)),
+        );
+    }
+
+    match &config.dir {
+        Some(dir) => Arc::new(
+            FileMailer::new(dir.clone(), config.from.clone())
+                .unwrap_or_else(|err| panic!("{}", err)),
+        ),
+        None => panic!("GCA_SMTP_HOST or GCA_MAIL_DIR must be set"),
+    }
+}
@@ -0,0 +1,69 @@
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{Mail, Mailer};
+use crate::config::{SmtpConfig, SmtpTls};
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+pub(super) fn build_message(from: &Mailbox, mail: Mail) -> Result<Message, String> {
+    let to = mail
+        .to
+        .parse::<Mailbox>()
+        .map_err(|err| format!("Invalid recipient {}: {}", mail.to, err))?;
+
+    Message::builder()
+        .from(from.clone())
+        .to(to)
+        .subject(mail.subject)
+        .body(mail.body)
+        .map_err(|err| format!("Unable to build mail: {}", err))
+}
+
+impl SmtpMailer {
+    pub fn new(config: &SmtpConfig, from: Mailbox) -> Result<Self, String> {
+        let host = &config.host;
+        let mut builder = match config.tls {
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            SmtpTls::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                host,
+            )),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+        }
+        .map_err(|err| format!("Invalid SMTP relay {}: {}", host, err))?;
+
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+
+        if let Some((username, password)) = &config.credentials {
+            builder = builder.credentials(Credentials::new(
+                username.clone(),
+                password.expose().to_string(),
+            ));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> Result<(), String> {
+        let message = build_message(&self.from, mail)?;
+
+        self.transport
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|err| format!("Unable to send mail: {}", err))
+    }
+}
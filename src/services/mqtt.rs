@@ -1,7 +1,10 @@
 mod mqtt_actions;
 mod mqtt_connector;
+#[allow(clippy::redundant_static_lifetimes)]
 mod mqtt_constants;
 
-pub use mqtt_actions::{publish_online_status, publish_open_door};
+pub use mqtt_actions::{publish_duress_alarm, publish_online_status, publish_open_door};
 pub use mqtt_connector::{init_main_client, init_route_client};
-pub use mqtt_constants::{MQTT_STATUS_TOPIC, MQTT_UNLOCK_TOPIC, OFFLINE_STATUS, ONLINE_STATUS};
+pub use mqtt_constants::{
+    MQTT_DURESS_TOPIC, MQTT_STATUS_TOPIC, MQTT_UNLOCK_TOPIC, OFFLINE_STATUS, ONLINE_STATUS,
+};
@@ -1,6 +1,10 @@
 use rumqttc::{AsyncClient, QoS};
 
-use crate::services::mqtt::{MQTT_STATUS_TOPIC, MQTT_UNLOCK_TOPIC, ONLINE_STATUS};
+use serde_json::json;
+
+use crate::services::mqtt::{
+    MQTT_DURESS_TOPIC, MQTT_STATUS_TOPIC, MQTT_UNLOCK_TOPIC, ONLINE_STATUS,
+};
 
 pub async fn publish_online_status(cli: &AsyncClient) {
     cli.publish(
@@ -30,3 +34,26 @@ pub async fn publish_open_door(cli: &AsyncClient) {
         Err(e) => log::error!("Error unlocking door: {:?}", e),
     }
 }
+
+// Silent alarm, only monitoring subscribes to this topic, the door itself is not aware of it.
+// `door_id` is null for the password unlock, which doesn't know the door
+pub async fn publish_duress_alarm(cli: &AsyncClient, user_id: i32, door_id: Option<u32>) {
+    let payload = json!({
+        "user_id": user_id,
+        "door_id": door_id,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+
+    let result = cli
+        .publish(
+            MQTT_DURESS_TOPIC,
+            QoS::AtLeastOnce,
+            false,
+            payload.to_string().into_bytes(),
+        )
+        .await;
+
+    if let Err(e) = result {
+        log::error!("Error publishing duress alarm: {:?}", e);
+    }
+}
@@ -3,6 +3,7 @@ use rumqttc::{AsyncClient, EventLoop, LastWill, MqttOptions, QoS};
 use std::{thread, time::Duration};
 use tokio::task;
 
+use crate::config::MqttConfig;
 use crate::services::mqtt::{publish_online_status, MQTT_STATUS_TOPIC, OFFLINE_STATUS};
 
 async fn connection_poll(mut con: EventLoop) {
@@ -20,23 +21,18 @@ async fn connection_poll(mut con: EventLoop) {
     }
 }
 
-fn setup_mqtt_options(id: String) -> MqttOptions {
-    let mqtt_host = std::env::var("GCA_DOOR_MQTT_HOST").expect("GCA_MQTT_HOST not set");
-    let mqtt_port = std::env::var("GCA_DOOR_MQTT_PORT").expect("GCA_MQTT_PORT not set");
-    let mqtt_user = std::env::var("GCA_DOOR_MQTT_USER").expect("GCA_MQTT_USER not set");
-    let mqtt_pass = std::env::var("GCA_DOOR_MQTT_PASS").expect("GCA_MQTT_PASS not set");
-
-    let mut options = MqttOptions::new(id, mqtt_host, mqtt_port.parse::<u16>().unwrap());
+fn setup_mqtt_options(id: String, config: &MqttConfig) -> MqttOptions {
+    let mut options = MqttOptions::new(id, config.host.clone(), config.port);
     options
-        .set_credentials(mqtt_user, mqtt_pass)
+        .set_credentials(config.user.clone(), config.pass.expose())
         .set_clean_session(true)
         .set_keep_alive(Duration::from_secs(5));
 
     options
 }
 
-pub fn init_main_client() {
-    let mut mqtt_options = setup_mqtt_options("api-gateway-will".to_string());
+pub fn init_main_client(config: &MqttConfig) {
+    let mut mqtt_options = setup_mqtt_options("api-gateway-will".to_string(), config);
     let will = LastWill::new(MQTT_STATUS_TOPIC, OFFLINE_STATUS, QoS::AtLeastOnce, true);
     mqtt_options.set_last_will(will);
 
@@ -48,8 +44,8 @@ pub fn init_main_client() {
     });
 }
 
-pub fn init_route_client() -> AsyncClient {
-    let mqtt_options_route = setup_mqtt_options("api-gateway-main".to_string());
+pub fn init_route_client(config: &MqttConfig) -> AsyncClient {
+    let mqtt_options_route = setup_mqtt_options("api-gateway-main".to_string(), config);
     let (route_cli, route_loop) = AsyncClient::new(mqtt_options_route, 10);
     task::spawn(connection_poll(route_loop));
 
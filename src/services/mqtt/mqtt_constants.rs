@@ -1,4 +1,5 @@
 pub const ONLINE_STATUS: &'static str = "online";
 pub const OFFLINE_STATUS: &'static str = "offline";
 pub const MQTT_STATUS_TOPIC: &'static str = "gca/api-gateway/status";
-pub const MQTT_UNLOCK_TOPIC: &'static str = "gca/api-gateway/unlock";
\ No newline at end of file
+pub const MQTT_UNLOCK_TOPIC: &'static str = "gca/api-gateway/unlock";
+pub const MQTT_DURESS_TOPIC: &'static str = "gca/api-gateway/alarm/duress";
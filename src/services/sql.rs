@@ -1,3 +1,3 @@
 mod sql_connector;
 
-pub use sql_connector::establish_connection;
\ No newline at end of file
+pub use sql_connector::establish_connection;
@@ -0,0 +1,3 @@
+mod failure_limiter;
+
+pub use failure_limiter::FailureLimiter;
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Failures {
+    count: u32,
+    first_at: Instant,
+}
+
+// Counts failed attempts per key (e.g. a door id) inside a fixed time window,
+// once `max_failures` is reached the key stays blocked until the window expires
+pub struct FailureLimiter {
+    max_failures: u32,
+    window: Duration,
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl FailureLimiter {
+    pub fn new(max_failures: u32, window: Duration) -> Self {
+        Self {
+            max_failures,
+            window,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_blocked(&self, key: &str) -> bool {
+        let mut failures = self.failures.lock().unwrap();
+
+        match failures.get(key) {
+            Some(entry) if entry.first_at.elapsed() >= self.window => {
+                failures.remove(key);
+                false
+            }
+            Some(entry) => entry.count >= self.max_failures,
+            None => false,
+        }
+    }
+
+    pub fn max_failures(&self) -> u32 {
+        self.max_failures
+    }
+
+    // Failures counted for the key in the current window
+    pub fn failures(&self, key: &str) -> u32 {
+        let failures = self.failures.lock().unwrap();
+
+        match failures.get(key) {
+            Some(entry) if entry.first_at.elapsed() < self.window => entry.count,
+            _ => 0,
+        }
+    }
+
+    pub fn register_failure(&self, key: &str) {
+        let mut failures = self.failures.lock().unwrap();
+        let window = self.window;
+
+        // Drop expired entries so the map doesn't grow forever
+        failures.retain(|_, entry| entry.first_at.elapsed() < window);
+
+        let entry = failures.entry(key.to_string()).or_insert(Failures {
+            count: 0,
+            first_at: Instant::now(),
+        });
+        entry.count += 1;
+
+        if entry.count == self.max_failures {
+            log::warn!("Too many failed attempts for '{}', blocking", key);
+        }
+    }
+
+    pub fn reset(&self, key: &str) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::FailureLimiter;
+
+    #[test]
+    fn blocks_after_max_failures() {
+        let limiter = FailureLimiter::new(3, Duration::from_secs(60));
+
+        limiter.register_failure("door-1");
+        limiter.register_failure("door-1");
+        assert!(!limiter.is_blocked("door-1"));
+        assert_eq!(limiter.failures("door-1"), 2);
+
+        limiter.register_failure("door-1");
+        assert!(limiter.is_blocked("door-1"));
+    }
+
+    #[test]
+    fn keys_are_counted_apart() {
+        let limiter = FailureLimiter::new(1, Duration::from_secs(60));
+
+        limiter.register_failure("door-1");
+        assert!(limiter.is_blocked("door-1"));
+        assert!(!limiter.is_blocked("door-2"));
+        assert_eq!(limiter.failures("door-2"), 0);
+    }
+
+    #[test]
+    fn reset_clears_the_failures() {
+        let limiter = FailureLimiter::new(1, Duration::from_secs(60));
+
+        limiter.register_failure("door-1");
+        limiter.reset("door-1");
+        assert!(!limiter.is_blocked("door-1"));
+        assert_eq!(limiter.failures("door-1"), 0);
+    }
+
+    #[test]
+    fn block_expires_with_the_window() {
+        let limiter = FailureLimiter::new(1, Duration::from_millis(20));
+
+        limiter.register_failure("door-1");
+        assert!(limiter.is_blocked("door-1"));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(!limiter.is_blocked("door-1"));
+        assert_eq!(limiter.failures("door-1"), 0);
+    }
+}
@@ -1,7 +1,9 @@
 mod error_handlers;
-mod response_builder;
 pub mod errors;
+pub mod i18n;
+pub mod pagination;
+mod response_builder;
+pub mod validation;
 
-pub use error_handlers::{error_mapper, Error, MappedErrors};
+pub use error_handlers::{ensure_affected, error_mapper, Error, MappedErrors};
 pub use response_builder::{build_response, Response};
-
@@ -1,21 +1,42 @@
 use std::fmt;
 
 use deadpool_diesel::InteractError;
+use diesel::result::DatabaseErrorKind;
 
 #[derive(Debug)]
 pub enum MappedErrors {
     InternalServerError,
     NotFound,
+    // Duplicate of a unique key
+    Conflict,
+    // Rejected by a constraint of the database, like a missing required value
+    Validation,
+    // References a row that doesn't exist, or the row is still referenced
+    ForeignKey,
+    // The database can't be reached
+    Unavailable,
 }
 
 pub fn error_mapper<T: Error>(error: T) -> MappedErrors {
     error.as_infra_error()
 }
 
+// Updates and deletes that touched no row were aimed at a missing one
+pub fn ensure_affected(rows: usize) -> Result<(), MappedErrors> {
+    match rows {
+        0 => Err(MappedErrors::NotFound),
+        _ => Ok(()),
+    }
+}
+
 impl fmt::Display for MappedErrors {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             MappedErrors::NotFound => write!(f, "Not found"),
+            MappedErrors::Conflict => write!(f, "Already exists"),
+            MappedErrors::Validation => write!(f, "Invalid data"),
+            MappedErrors::ForeignKey => write!(f, "Related record missing or still in use"),
+            MappedErrors::Unavailable => write!(f, "Database unavailable"),
             MappedErrors::InternalServerError => write!(f, "Internal server error"),
         }
     }
@@ -27,17 +48,35 @@ pub trait Error {
 
 impl Error for diesel::result::Error {
     fn as_infra_error(&self) -> MappedErrors {
-        log::error!("Error: {:?}", self);
         match self {
             diesel::result::Error::NotFound => MappedErrors::NotFound,
-            _ => MappedErrors::InternalServerError,
+            diesel::result::Error::DatabaseError(kind, info) => {
+                log::warn!("Database error: {:?} {}", kind, info.message());
+                match kind {
+                    DatabaseErrorKind::UniqueViolation => MappedErrors::Conflict,
+                    DatabaseErrorKind::ForeignKeyViolation => MappedErrors::ForeignKey,
+                    DatabaseErrorKind::NotNullViolation | DatabaseErrorKind::CheckViolation => {
+                        MappedErrors::Validation
+                    }
+                    DatabaseErrorKind::ClosedConnection => MappedErrors::Unavailable,
+                    _ => {
+                        log::error!("Error: {:?}", self);
+                        MappedErrors::InternalServerError
+                    }
+                }
+            }
+            _ => {
+                log::error!("Error: {:?}", self);
+                MappedErrors::InternalServerError
+            }
         }
     }
 }
 
 impl Error for deadpool_diesel::PoolError {
     fn as_infra_error(&self) -> MappedErrors {
-        MappedErrors::InternalServerError
+        log::error!("Pool error: {:?}", self);
+        MappedErrors::Unavailable
     }
 }
 
@@ -46,3 +85,49 @@ impl Error for InteractError {
         MappedErrors::InternalServerError
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database_error(kind: DatabaseErrorKind) -> diesel::result::Error {
+        diesel::result::Error::DatabaseError(kind, Box::new("refused".to_string()))
+    }
+
+    #[test]
+    fn rows_affected_or_not_found() {
+        assert!(matches!(ensure_affected(0), Err(MappedErrors::NotFound)));
+        assert!(ensure_affected(1).is_ok());
+        assert!(ensure_affected(3).is_ok());
+    }
+
+    #[test]
+    fn maps_database_errors() {
+        let mapped = |error: diesel::result::Error| error_mapper(error);
+
+        assert!(matches!(
+            mapped(diesel::result::Error::NotFound),
+            MappedErrors::NotFound
+        ));
+        assert!(matches!(
+            mapped(database_error(DatabaseErrorKind::UniqueViolation)),
+            MappedErrors::Conflict
+        ));
+        assert!(matches!(
+            mapped(database_error(DatabaseErrorKind::ForeignKeyViolation)),
+            MappedErrors::ForeignKey
+        ));
+        assert!(matches!(
+            mapped(database_error(DatabaseErrorKind::NotNullViolation)),
+            MappedErrors::Validation
+        ));
+        assert!(matches!(
+            mapped(database_error(DatabaseErrorKind::ClosedConnection)),
+            MappedErrors::Unavailable
+        ));
+        assert!(matches!(
+            mapped(diesel::result::Error::RollbackTransaction),
+            MappedErrors::InternalServerError
+        ));
+    }
+}
@@ -1,5 +1,7 @@
 mod controller_errors;
 mod models_errors;
+mod password_errors;
 
-pub use controller_errors::{ControllerError, ControllerErrorType};
+pub use controller_errors::{ControllerError, ControllerErrorType, FieldError, Problem};
 pub use models_errors::ModelError;
+pub use password_errors::PasswordError;
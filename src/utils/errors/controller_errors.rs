@@ -4,13 +4,51 @@ use axum::{
     Json,
 };
 use core::fmt;
+use log::error;
+use serde::Serialize;
+use utoipa::ToSchema;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
 
-use serde_json::json;
+use crate::middlewares::request_context;
+use crate::utils::{i18n, MappedErrors};
 
+// Problem with a single field of the request, listed on validation errors
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+// Rendered as RFC 7807 `application/problem+json`. `code` is stable, clients can branch on
+// it, the message is in the language the client asked for
 #[derive(Debug)]
 pub struct ControllerError {
     pub message: String,
     pub status_code: StatusCode,
+    pub code: &'static str,
+    pub errors: Vec<FieldError>,
+}
+
+// Code of the errors that didn't get a more specific one
+fn default_code(status_code: StatusCode) -> &'static str {
+    match status_code {
+        StatusCode::BAD_REQUEST => "bad_request",
+        StatusCode::UNAUTHORIZED => "unauthorized",
+        StatusCode::FORBIDDEN => "forbidden",
+        StatusCode::NOT_FOUND => "not_found",
+        StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+        StatusCode::CONFLICT => "conflict",
+        StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+        StatusCode::UNPROCESSABLE_ENTITY => "validation_failed",
+        StatusCode::LOCKED => "locked",
+        StatusCode::TOO_MANY_REQUESTS => "too_many_requests",
+        StatusCode::BAD_GATEWAY => "bad_gateway",
+        StatusCode::SERVICE_UNAVAILABLE => "unavailable",
+        _ if status_code.is_client_error() => "bad_request",
+        _ => "internal_error",
+    }
 }
 
 impl ControllerError {
@@ -18,57 +56,220 @@ impl ControllerError {
         Self {
             message,
             status_code,
+            code: default_code(status_code),
+            errors: Vec::new(),
         }
     }
 
+    // Message taken from the catalog, `code` is its key
+    pub fn localized(status_code: StatusCode, code: &'static str) -> Self {
+        Self::localized_with(status_code, code, &[])
+    }
+
+    pub fn localized_with(
+        status_code: StatusCode,
+        code: &'static str,
+        params: &[(&str, &str)],
+    ) -> Self {
+        Self::new(i18n::text(code, params), status_code).with_code(code)
+    }
+
+    // Generic error of the status, for the responses that have nothing more to say
+    pub fn from_status(status_code: StatusCode) -> Self {
+        Self::localized(status_code, default_code(status_code))
+    }
+
     pub fn validation_error(message: String) -> Self {
-        Self {
-            message,
-            status_code: StatusCode::BAD_REQUEST,
-        }
+        Self::new(message, StatusCode::BAD_REQUEST)
+    }
+
+    // Query string that parses but makes no sense, e.g. an unknown sort field
+    pub fn invalid_query_param(param: &str) -> Self {
+        Self::localized_with(
+            StatusCode::BAD_REQUEST,
+            "invalid_query_param",
+            &[("param", param)],
+        )
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
     }
 
     pub fn from_type(error_type: ControllerErrorType) -> Self {
-        let (message, status_code) = match error_type {
-            ControllerErrorType::BodyParsingError => {
-                ("Request body inválido".to_string(), StatusCode::BAD_REQUEST)
-            }
-            ControllerErrorType::NotFound => {
-                ("Recurso não encontrado".to_string(), StatusCode::NOT_FOUND)
+        let (status_code, code) = match error_type {
+            ControllerErrorType::BodyParsingError => (StatusCode::BAD_REQUEST, "invalid_body"),
+            ControllerErrorType::ValidationError => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
             }
-            ControllerErrorType::Unauthorized => {
-                ("Não autorizado".to_string(), StatusCode::UNAUTHORIZED)
+            ControllerErrorType::NotFound => (StatusCode::NOT_FOUND, "not_found"),
+            ControllerErrorType::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized"),
+            ControllerErrorType::InternalServerError => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
             }
-            ControllerErrorType::InternalServerError => (
-                "Erro interno".to_string(),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ),
         };
 
-        Self {
-            message,
-            status_code,
-        }
+        Self::localized(status_code, code)
     }
 }
 
 pub enum ControllerErrorType {
     BodyParsingError,
+    ValidationError,
     NotFound,
     Unauthorized,
     InternalServerError,
 }
 
+// Same status code for the same model error, whatever the controller
+impl From<MappedErrors> for ControllerError {
+    fn from(error: MappedErrors) -> Self {
+        let (status_code, code) = match error {
+            MappedErrors::NotFound => (StatusCode::NOT_FOUND, "not_found"),
+            MappedErrors::Conflict => (StatusCode::CONFLICT, "conflict"),
+            MappedErrors::ForeignKey => (StatusCode::CONFLICT, "foreign_key_violation"),
+            MappedErrors::Validation => (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed"),
+            MappedErrors::Unavailable => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+            MappedErrors::InternalServerError => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")
+            }
+        };
+
+        Self::localized(status_code, code)
+    }
+}
+
+// Catalog message of a failed validator, e.g. `field.length.between` for a length with
+// both bounds. The rejected value is never echoed, it may be a password
+fn field_message(error: &ValidationError) -> String {
+    let param = |name: &str| {
+        error.params.get(name).map(|value| match value.as_str() {
+            Some(text) => text.to_string(),
+            None => value.to_string(),
+        })
+    };
+    let (min, max, equal) = (param("min"), param("max"), param("equal"));
+
+    let key = match error.code.as_ref() {
+        code @ ("length" | "range") => match (&min, &max, &equal) {
+            (_, _, Some(_)) => format!("field.{}.equal", code),
+            (Some(_), Some(_), _) => format!("field.{}.between", code),
+            (Some(_), None, _) => format!("field.{}.min", code),
+            (None, Some(_), _) => format!("field.{}.max", code),
+            (None, None, None) => "field.invalid".to_string(),
+        },
+        code => format!("field.{}", code),
+    };
+
+    let params = [("min", min), ("max", max), ("equal", equal)];
+    let params = params
+        .iter()
+        .filter_map(|(name, value)| value.as_deref().map(|value| (*name, value)))
+        .collect::<Vec<_>>();
+
+    match i18n::text(&key, &params) {
+        message if message == key => i18n::text("field.invalid", &[]),
+        message => message,
+    }
+}
+
+fn field_errors(prefix: &str, errors: &ValidationErrors, list: &mut Vec<FieldError>) {
+    let join = |field: &str| {
+        if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        }
+    };
+
+    for (field, kind) in errors.errors() {
+        let path = join(field);
+
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                list.extend(errors.iter().map(|error| {
+                    // Rules spanning several fields are all under `__all__`, they name the
+                    // field they report on themselves
+                    let field = match error.params.get("field").and_then(|name| name.as_str()) {
+                        Some(name) if *field == "__all__" => join(name),
+                        _ => path.clone(),
+                    };
+
+                    FieldError {
+                        field,
+                        code: error.code.to_string(),
+                        message: field_message(error),
+                    }
+                }))
+            }
+            ValidationErrorsKind::Struct(errors) => field_errors(&path, errors, list),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    field_errors(&format!("{}[{}]", path, index), errors, list);
+                }
+            }
+        }
+    }
+}
+
+// Every invalid field is listed, with the validator that refused it as code
+impl From<ValidationErrors> for ControllerError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut list = Vec::new();
+        field_errors("", &errors, &mut list);
+        list.sort_by(|a, b| a.field.cmp(&b.field));
+
+        Self::from_type(ControllerErrorType::ValidationError).with_errors(list)
+    }
+}
+
+// Body of every error response, also the schema of the errors in the OpenAPI document
+#[derive(Serialize, ToSchema)]
+pub struct Problem<'a> {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'a str,
+    status: u16,
+    detail: &'a str,
+    code: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    #[schema(value_type = Vec<FieldError>)]
+    errors: &'a [FieldError],
+}
+
 impl IntoResponse for ControllerError {
     fn into_response(self) -> axum::response::Response {
-        let status_code = self.status_code;
-        let header = [(header::CONTENT_TYPE, "application/json")];
-        let body = Json(json!({
-            "status_code": self.status_code.as_u16(),
-            "message": self.message,
-        }));
-
-        (status_code, header, body).into_response()
+        let request_id = request_context::current_request_id();
+
+        if self.status_code.is_server_error() {
+            error!(
+                "[{}] {} {}",
+                request_id.as_deref().unwrap_or("-"),
+                self.status_code,
+                self.message
+            );
+        }
+
+        let header = [(header::CONTENT_TYPE, "application/problem+json")];
+        let body = Json(Problem {
+            problem_type: "about:blank",
+            title: self.status_code.canonical_reason().unwrap_or("Error"),
+            status: self.status_code.as_u16(),
+            detail: &self.message,
+            code: self.code,
+            request_id,
+            errors: &self.errors,
+        });
+
+        (self.status_code, header, body).into_response()
     }
 }
 
@@ -1,4 +1,6 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use axum::response::IntoResponse;
+
+use super::{ControllerError, ControllerErrorType};
 
 pub enum ModelError {
     NotFound,
@@ -7,11 +9,13 @@ pub enum ModelError {
 
 impl IntoResponse for ModelError {
     fn into_response(self) -> axum::response::Response {
-        let message = match self {
-            ModelError::NotFound => "Model not found",
-            ModelError::InternalServerError => "Internal server error",
+        let error = match self {
+            ModelError::NotFound => ControllerError::from_type(ControllerErrorType::NotFound),
+            ModelError::InternalServerError => {
+                ControllerError::from_type(ControllerErrorType::InternalServerError)
+            }
         };
 
-        (StatusCode::INTERNAL_SERVER_ERROR, message).into_response()
+        error.into_response()
     }
 }
@@ -0,0 +1,49 @@
+use axum::{http::StatusCode, response::IntoResponse};
+
+use super::{ControllerError, FieldError};
+use crate::auth::password_policy::PolicyViolation;
+use crate::utils::MappedErrors;
+
+// Error of the handlers that set passwords, the policy violations are listed one by one
+#[derive(Debug)]
+pub enum PasswordError {
+    PolicyViolations(Vec<PolicyViolation>),
+    Controller(ControllerError),
+}
+
+impl From<ControllerError> for PasswordError {
+    fn from(error: ControllerError) -> Self {
+        PasswordError::Controller(error)
+    }
+}
+
+impl From<MappedErrors> for PasswordError {
+    fn from(error: MappedErrors) -> Self {
+        PasswordError::Controller(error.into())
+    }
+}
+
+impl IntoResponse for PasswordError {
+    fn into_response(self) -> axum::response::Response {
+        let violations = match self {
+            PasswordError::PolicyViolations(violations) => violations,
+            PasswordError::Controller(error) => return error.into_response(),
+        };
+
+        let errors = violations
+            .into_iter()
+            .map(|violation| FieldError {
+                field: "password".to_string(),
+                code: violation.rule.to_string(),
+                message: violation.message,
+            })
+            .collect();
+
+        ControllerError::localized(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "password_policy_violation",
+        )
+        .with_errors(errors)
+        .into_response()
+    }
+}
@@ -0,0 +1,315 @@
+use std::future::Future;
+
+// Languages the messages are translated to. Error codes are the same in all of them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    PtBr,
+    EnUs,
+}
+
+impl Locale {
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Locale::PtBr => "pt-BR",
+            Locale::EnUs => "en-US",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        let language = tag.split('-').next().unwrap_or(tag).to_lowercase();
+
+        match language.as_str() {
+            "pt" => Some(Locale::PtBr),
+            "en" => Some(Locale::EnUs),
+            "*" => Some(Locale::default()),
+            _ => None,
+        }
+    }
+
+    // First supported language of an `Accept-Language` header, by quality, e.g.
+    // "fr-CA, en;q=0.8, pt;q=0.5" is en-US. The default when none is supported
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut languages = header
+            .split(',')
+            .filter_map(|item| {
+                let mut parts = item.split(';').map(str::trim);
+                let tag = parts.next().filter(|tag| !tag.is_empty())?;
+                let quality = parts
+                    .find_map(|param| param.strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+
+                (quality > 0.0).then_some((tag, quality))
+            })
+            .collect::<Vec<_>>();
+
+        // Stable, languages with the same quality keep the client's order
+        languages.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        languages
+            .into_iter()
+            .find_map(|(tag, _)| Locale::from_tag(tag))
+            .unwrap_or_default()
+    }
+}
+
+tokio::task_local! {
+    static LOCALE: Locale;
+}
+
+// Language of the request being handled, set by the request context middleware
+pub fn current_locale() -> Locale {
+    LOCALE.try_with(|locale| *locale).unwrap_or_default()
+}
+
+pub async fn with_locale<F: Future>(locale: Locale, future: F) -> F::Output {
+    LOCALE.scope(locale, future).await
+}
+
+// Message of `key` in the current language, with `{name}` placeholders replaced by `params`.
+// Unknown keys are returned as is
+pub fn text(key: &str, params: &[(&str, &str)]) -> String {
+    let message = match (catalog(key), current_locale()) {
+        (Some((pt_br, _)), Locale::PtBr) => pt_br,
+        (Some((_, en_us)), Locale::EnUs) => en_us,
+        (None, _) => return key.to_string(),
+    };
+
+    params
+        .iter()
+        .fold(message.to_string(), |message, (name, value)| {
+            message.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+// (pt-BR, en-US) of every message. Error codes are the keys of their own message, field
+// validators are under `field.` and password rules under `password.`
+fn catalog(key: &str) -> Option<(&'static str, &'static str)> {
+    let messages = match key {
+        // Generic, by status
+        "bad_request" => ("Requisição inválida", "Bad request"),
+        "unauthorized" => ("Não autorizado", "Unauthorized"),
+        "forbidden" => ("Acesso negado", "Forbidden"),
+        "not_found" => ("Recurso não encontrado", "Resource not found"),
+        "method_not_allowed" => ("Método não permitido", "Method not allowed"),
+        "conflict" => ("Registro já existe", "Already exists"),
+        "payload_too_large" => ("Requisição grande demais", "Payload too large"),
+        "unsupported_media_type" => ("Tipo de conteúdo não suportado", "Unsupported media type"),
+        "validation_failed" => ("Dados inválidos", "Invalid data"),
+        "locked" => ("Recurso bloqueado", "Locked"),
+        "too_many_requests" => ("Muitas requisições", "Too many requests"),
+        "bad_gateway" => ("Erro no serviço externo", "Bad gateway"),
+        "unavailable" => ("Serviço indisponível", "Service unavailable"),
+        "internal_error" => ("Erro interno", "Internal server error"),
+
+        // Requests and data
+        "invalid_body" => ("Request body inválido", "Invalid request body"),
+        "swagger_ui_disabled" => ("Swagger UI não configurado", "Swagger UI is not configured"),
+        "invalid_json" => ("JSON malformado", "Malformed JSON"),
+        "json_content_type_required" => (
+            "O Content-Type deve ser application/json",
+            "Content-Type must be application/json",
+        ),
+        "invalid_query_param" => (
+            "Parâmetro '{param}' da consulta inválido",
+            "Invalid query parameter '{param}'",
+        ),
+        "foreign_key_violation" => (
+            "Registro relacionado inexistente ou ainda em uso",
+            "Related record missing or still in use",
+        ),
+        "password_policy_violation" => (
+            "A senha não atende à política",
+            "Password doesn't meet the policy",
+        ),
+        "missing_permission" => (
+            "Permissão '{permission}' necessária",
+            "Missing permission '{permission}'",
+        ),
+        "permission_not_held" => (
+            "Não é possível conceder a permissão '{permission}' sem tê-la",
+            "Can't grant the permission '{permission}' without holding it",
+        ),
+        "self_role_change" => (
+            "Não é possível alterar os próprios papéis",
+            "Can't change your own roles",
+        ),
+        "code_generation_failed" => (
+            "Não foi possível gerar um código livre, tente novamente",
+            "Unable to generate a free code, try again",
+        ),
+        "token_generation_failed" => ("Erro ao gerar o token", "Error generating token"),
+
+        // Login
+        "invalid_credentials" => ("Usuário inválido", "Invalid user"),
+        "account_locked" => (
+            "Usuário bloqueado até {until}",
+            "Account locked until {until}",
+        ),
+        "too_many_attempts" => (
+            "Muitas tentativas inválidas, tente novamente mais tarde",
+            "Too many failed attempts, try again later",
+        ),
+        "invalid_refresh_token" => ("Refresh token inválido", "Invalid refresh token"),
+        "invalid_reset_token" => (
+            "Token de redefinição inválido ou expirado",
+            "Invalid or expired reset token",
+        ),
+        "invalid_current_password" => ("Senha atual inválida", "Current password is invalid"),
+        "invalid_mfa_token" => ("Token MFA inválido", "Invalid MFA token"),
+        "invalid_totp_code" => ("Código TOTP inválido", "Invalid TOTP code"),
+        "invalid_recovery_code" => ("Código de recuperação inválido", "Invalid recovery code"),
+        "totp_not_enabled" => ("TOTP não está habilitado", "TOTP is not enabled"),
+        "totp_already_enabled" => ("TOTP já está habilitado", "TOTP is already enabled"),
+        "totp_enrollment_not_started" => (
+            "Cadastro do TOTP não iniciado",
+            "TOTP enrollment not started",
+        ),
+        "totp_required" => (
+            "TOTP é obrigatório para administradores",
+            "TOTP is mandatory for admins",
+        ),
+        "oidc_disabled" => ("Login OIDC não configurado", "OIDC login is not configured"),
+        "oidc_provider_error" => (
+            "Erro no provedor de identidade: {reason}",
+            "Identity provider error: {reason}",
+        ),
+        "oidc_invalid_state" => (
+            "Estado de login desconhecido ou expirado",
+            "Unknown or expired login state",
+        ),
+        "oidc_invalid_id_token" => ("ID token inválido", "Invalid ID token"),
+        "oidc_email_not_verified" => (
+            "Email não verificado pelo provedor",
+            "Email not verified by the provider",
+        ),
+
+        // Doors and credentials
+        "door_unlocked" => ("Porta destrancada", "Door unlocked"),
+        "door_not_found" => ("Porta desconhecida", "Unknown door"),
+        "no_access_now" => (
+            "Usuário não tem acesso no momento",
+            "User has no access at the moment",
+        ),
+        "invalid_pin" => ("PIN inválido", "Invalid PIN"),
+        "invalid_badge" => ("Cartão inválido", "Invalid badge"),
+        "invalid_visit_code" => ("Código de visitante inválido", "Invalid visitor code"),
+        "badge_already_issued" => ("Cartão já emitido", "Badge already issued"),
+        "badge_revoked" => (
+            "Cartão revogado não pode ser alterado",
+            "A revoked badge can't be changed",
+        ),
+        "door_tokens_disabled" => (
+            "Tokens de porta não estão configurados",
+            "Door tokens are not configured",
+        ),
+
+        // Fields
+        "field.invalid" => ("Valor inválido", "Invalid value"),
+        "field.email" => ("Email inválido", "Invalid email"),
+        "field.length.min" => (
+            "Deve ter no mínimo {min} caracteres",
+            "Must have at least {min} characters",
+        ),
+        "field.length.max" => (
+            "Deve ter no máximo {max} caracteres",
+            "Must have at most {max} characters",
+        ),
+        "field.length.between" => (
+            "Deve ter entre {min} e {max} caracteres",
+            "Must have between {min} and {max} characters",
+        ),
+        "field.length.equal" => (
+            "Deve ter {equal} caracteres",
+            "Must have {equal} characters",
+        ),
+        "field.range.min" => ("Deve ser no mínimo {min}", "Must be at least {min}"),
+        "field.range.max" => ("Deve ser no máximo {max}", "Must be at most {max}"),
+        "field.range.between" => (
+            "Deve estar entre {min} e {max}",
+            "Must be between {min} and {max}",
+        ),
+        "field.pin_not_numeric" => ("Deve conter apenas dígitos", "Must contain only digits"),
+        "field.invalid_badge_uid" => (
+            "Deve ter entre 4 e 64 letras ou dígitos",
+            "Must have between 4 and 64 letters or digits",
+        ),
+        "field.unknown_permission" => ("Permissão desconhecida", "Unknown permission"),
+        "field.after_start" => ("Deve ser depois do início", "Must be after the start"),
+        "field.code_or_recovery_code" => (
+            "Informe o código ou um código de recuperação",
+            "A code or a recovery code is required",
+        ),
+
+        // Password policy
+        "password.min_length" => (
+            "Deve ter no mínimo {min} caracteres",
+            "Must have at least {min} characters",
+        ),
+        "password.uppercase" => (
+            "Deve ter uma letra maiúscula",
+            "Must have an uppercase letter",
+        ),
+        "password.lowercase" => (
+            "Deve ter uma letra minúscula",
+            "Must have a lowercase letter",
+        ),
+        "password.digit" => ("Deve ter um dígito", "Must have a digit"),
+        "password.symbol" => ("Deve ter um símbolo", "Must have a symbol"),
+        "password.blacklist" => ("É comum demais", "Is too common"),
+        "password.history" => (
+            "Deve ser diferente das últimas {count} senhas",
+            "Must differ from the last {count} passwords",
+        ),
+
+        _ => return None,
+    };
+
+    Some(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_best_supported_language() {
+        assert_eq!(Locale::from_accept_language("en-US"), Locale::EnUs);
+        assert_eq!(Locale::from_accept_language("pt-BR,en;q=0.9"), Locale::PtBr);
+        assert_eq!(
+            Locale::from_accept_language("fr-CA, en;q=0.8, pt;q=0.5"),
+            Locale::EnUs
+        );
+        assert_eq!(
+            Locale::from_accept_language("pt;q=0.4, EN-gb;q=0.7"),
+            Locale::EnUs
+        );
+    }
+
+    #[test]
+    fn keeps_the_client_order_on_equal_quality() {
+        assert_eq!(Locale::from_accept_language("en, pt"), Locale::EnUs);
+        assert_eq!(Locale::from_accept_language("pt, en"), Locale::PtBr);
+    }
+
+    #[test]
+    fn falls_back_to_the_default() {
+        assert_eq!(Locale::from_accept_language(""), Locale::PtBr);
+        assert_eq!(Locale::from_accept_language("fr, de;q=0.5"), Locale::PtBr);
+        assert_eq!(Locale::from_accept_language("*"), Locale::PtBr);
+        assert_eq!(Locale::from_accept_language("en;q=0, fr"), Locale::PtBr);
+        assert_eq!(Locale::from_accept_language("en;q=abc"), Locale::PtBr);
+    }
+
+    #[tokio::test]
+    async fn translates_with_params() {
+        let params = [("permission", "users:read")];
+
+        let pt_br = with_locale(Locale::PtBr, async { text("missing_permission", &params) }).await;
+        let en_us = with_locale(Locale::EnUs, async { text("missing_permission", &params) }).await;
+
+        assert_eq!(pt_br, "Permissão 'users:read' necessária");
+        assert_eq!(en_us, "Missing permission 'users:read'");
+        assert_eq!(text("no.such.key", &[]), "no.such.key");
+    }
+}
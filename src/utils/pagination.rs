@@ -0,0 +1,232 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::user::ListUser;
+use crate::models::users_accesses::UserAccess;
+use crate::utils::errors::ControllerError;
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 200;
+
+// Query parameters shared by the paginated listings. `cursor` comes from the `next_cursor`
+// of the previous page, `page` (starting at 1) is there for clients that jump around.
+// `sort` is a field name, with a leading `-` for descending order
+#[derive(Deserialize, Debug, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub page: Option<i64>,
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sort {
+    pub field: String,
+    pub descending: bool,
+}
+
+// Page to load, ready to be pushed down into the queries
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub limit: i64,
+    pub offset: i64,
+    pub sort: Option<Sort>,
+}
+
+#[derive(Serialize, Debug, ToSchema)]
+#[aliases(UsersPage = Page<ListUser>, AccessesPage = Page<UserAccess>)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub limit: i64,
+    pub next_cursor: Option<String>,
+}
+
+// Cursors are opaque to the clients, they only carry the offset of the next page for now
+fn encode_cursor(offset: i64) -> String {
+    URL_SAFE_NO_PAD.encode(offset.to_string())
+}
+
+fn decode_cursor(cursor: &str) -> Option<i64> {
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+
+    String::from_utf8(bytes)
+        .ok()?
+        .parse::<i64>()
+        .ok()
+        .filter(|offset| *offset >= 0)
+}
+
+impl PageQuery {
+    // Only the fields in `sort_fields` can be used to sort
+    pub fn resolve(&self, sort_fields: &[&str]) -> Result<PageRequest, ControllerError> {
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+        let offset = match (&self.cursor, self.page) {
+            (Some(cursor), _) => decode_cursor(cursor)
+                .ok_or_else(|| ControllerError::invalid_query_param("cursor"))?,
+            (None, Some(page)) if page >= 1 => (page - 1)
+                .checked_mul(limit)
+                .ok_or_else(|| ControllerError::invalid_query_param("page"))?,
+            (None, Some(_)) => return Err(ControllerError::invalid_query_param("page")),
+            (None, None) => 0,
+        };
+
+        let sort = match &self.sort {
+            Some(sort) => {
+                let (field, descending) = match sort.strip_prefix('-') {
+                    Some(field) => (field, true),
+                    None => (sort.as_str(), false),
+                };
+
+                if !sort_fields.contains(&field) {
+                    return Err(ControllerError::invalid_query_param("sort"));
+                }
+
+                Some(Sort {
+                    field: field.to_string(),
+                    descending,
+                })
+            }
+            None => None,
+        };
+
+        Ok(PageRequest {
+            limit,
+            offset,
+            sort,
+        })
+    }
+}
+
+// LIKE pattern matching `text` anywhere, its wildcards are taken literally
+pub fn contains_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
+impl<T> Page<T> {
+    pub fn new(items: Vec<T>, total: i64, page: &PageRequest) -> Self {
+        let next_offset = page.offset.saturating_add(items.len() as i64);
+        let next_cursor = if !items.is_empty() && next_offset < total {
+            Some(encode_cursor(next_offset))
+        } else {
+            None
+        };
+
+        Self {
+            items,
+            total,
+            limit: page.limit,
+            next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SORT_FIELDS: &[&str] = &["id", "email"];
+
+    fn query(limit: Option<i64>, page: Option<i64>) -> PageQuery {
+        PageQuery {
+            limit,
+            page,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clamps_the_limit() {
+        let low = query(Some(0), None).resolve(SORT_FIELDS).unwrap();
+        let high = query(Some(10_000), None).resolve(SORT_FIELDS).unwrap();
+        let default = query(None, None).resolve(SORT_FIELDS).unwrap();
+
+        assert_eq!(low.limit, 1);
+        assert_eq!(high.limit, MAX_LIMIT);
+        assert_eq!(default.limit, DEFAULT_LIMIT);
+    }
+
+    #[test]
+    fn turns_pages_into_offsets() {
+        let first = query(Some(20), Some(1)).resolve(SORT_FIELDS).unwrap();
+        let third = query(Some(20), Some(3)).resolve(SORT_FIELDS).unwrap();
+
+        assert_eq!(first.offset, 0);
+        assert_eq!(third.offset, 40);
+    }
+
+    #[test]
+    fn refuses_pages_out_of_range() {
+        assert!(query(None, Some(0)).resolve(SORT_FIELDS).is_err());
+        assert!(query(None, Some(-1)).resolve(SORT_FIELDS).is_err());
+        assert!(query(Some(MAX_LIMIT), Some(i64::MAX))
+            .resolve(SORT_FIELDS)
+            .is_err());
+    }
+
+    #[test]
+    fn follows_the_cursor_of_the_previous_page() {
+        let first = query(Some(2), None).resolve(SORT_FIELDS).unwrap();
+        let page = Page::new(vec![1, 2], 5, &first);
+
+        let next = PageQuery {
+            limit: Some(2),
+            cursor: page.next_cursor,
+            ..Default::default()
+        }
+        .resolve(SORT_FIELDS)
+        .unwrap();
+
+        assert_eq!(next.offset, 2);
+        assert!(Page::new(vec![5], 5, &PageRequest { offset: 4, ..next })
+            .next_cursor
+            .is_none());
+    }
+
+    #[test]
+    fn refuses_invalid_cursors() {
+        let negative = PageQuery {
+            cursor: Some(encode_cursor(-1)),
+            ..Default::default()
+        };
+        let garbage = PageQuery {
+            cursor: Some("not a cursor".to_string()),
+            ..Default::default()
+        };
+
+        assert!(negative.resolve(SORT_FIELDS).is_err());
+        assert!(garbage.resolve(SORT_FIELDS).is_err());
+    }
+
+    #[test]
+    fn sorts_only_by_the_allowed_fields() {
+        let sort = |sort: &str| {
+            PageQuery {
+                sort: Some(sort.to_string()),
+                ..Default::default()
+            }
+            .resolve(SORT_FIELDS)
+        };
+
+        let descending = sort("-email").unwrap().sort.unwrap();
+
+        assert_eq!(descending.field, "email");
+        assert!(descending.descending);
+        assert!(!sort("id").unwrap().sort.unwrap().descending);
+        assert!(sort("password").is_err());
+    }
+
+    #[test]
+    fn escapes_like_wildcards() {
+        assert_eq!(contains_pattern("a_b%c\\"), "%a\\_b\\%c\\\\%");
+    }
+}
@@ -1,7 +1,8 @@
 use axum::{http::StatusCode, Json};
 use serde::Serialize;
+use utoipa::ToSchema;
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct Response {
     pub message: String,
 }
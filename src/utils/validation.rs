@@ -0,0 +1,62 @@
+use std::ops::Deref;
+
+use axum::async_trait;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRequest, Json, Request};
+use log::debug;
+use serde::de::DeserializeOwned;
+use validator::{Validate, ValidationError};
+
+use crate::utils::errors::ControllerError;
+
+// JSON body deserialized and then checked with `validator`. Bodies that don't parse keep the
+// status axum gives them, invalid ones are a 422 listing every invalid field
+pub struct ValidatedJson<T>(pub T);
+
+// The text of axum is English only and tells about our types, it only goes to the logs
+fn rejection_error(rejection: JsonRejection) -> ControllerError {
+    debug!("JSON body rejected: {}", rejection.body_text());
+
+    let code = match rejection {
+        JsonRejection::MissingJsonContentType(_) => "json_content_type_required",
+        JsonRejection::JsonSyntaxError(_) => "invalid_json",
+        _ => "invalid_body",
+    };
+
+    ControllerError::localized(rejection.status(), code)
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ControllerError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(rejection_error)?;
+
+        value.validate()?;
+
+        Ok(ValidatedJson(value))
+    }
+}
+
+impl<T> Deref for ValidatedJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// Error of a rule spanning several fields, for `#[validate(schema(...))]`. Reported on
+// `field`, like the errors of a single field
+pub fn cross_field_error(field: &'static str, code: &'static str) -> ValidationError {
+    let mut error = ValidationError::new(code);
+    error.add_param("field".into(), &field);
+    error
+}